@@ -5,49 +5,106 @@
 
 // Standard library imports.
 use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
     convert::Infallible,
+    future::Future,
+    io::Write,
     net::SocketAddr,
+    pin::Pin,
     sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
     },
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 // Third-party imports.
 use dashmap::DashMap;
+use once_cell::sync::{Lazy, OnceCell};
 use hyper::{
+    server::{
+        accept::Accept,
+        conn::{AddrIncoming, AddrStream},
+    },
     service::{make_service_fn, service_fn},
     Body, Request, Response, Server, StatusCode,
 };
 use reqwest::Client;
 use url::Url;
 use serde::{Deserialize, Serialize};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
 use tauri::{Emitter, Listener, Window};
-use tokio::sync::oneshot;
+use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_dialog::DialogExt;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::oneshot,
+};
+use tokio_util::sync::CancellationToken;
 
 use std::path::{Path, PathBuf};
-use tauri::{command, AppHandle, Manager};
+use tauri::{
+    command, AppHandle, Manager, State, PhysicalPosition, PhysicalSize, WebviewUrl, WebviewWindow,
+    WebviewWindowBuilder, WindowEvent,
+};
 
 use std::fs;
 
-// Import the Tauri plugins
-use tauri_plugin_dialog;
+/// Print a line to stdout and keep it in `LOG_LINES`, where support
+/// bundles pick it up.
+macro_rules! log_line {
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        println!("{}", line);
+        record_log_line(line);
+    }};
+}
+
+/// Like `log_line!`, but for errors and warnings, which go to stderr.
+macro_rules! log_error {
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        eprintln!("{}", line);
+        record_log_line(line);
+    }};
+}
 
-// Add a command to save files using the standard Rust fs module
-#[tauri::command]
-async fn save_file(path: String, contents: Vec<u8>) -> Result<(), String> {
-    use std::fs::File;
-    use std::io::Write;
+/// Payload of the `file-write-progress` event.
+#[derive(Serialize, Clone)]
+struct FileWriteProgress {
+    path: String,
+    bytes_written: u64,
+    total: u64,
+}
 
-    println!("Saving file to: {}", path);
+/// Write `contents` to `path` in `Config::write_chunk_bytes` pieces,
+/// emitting `file-write-progress` after each so the UI can show progress
+/// on large exports.
+fn write_with_progress(app_handle: &AppHandle, path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    let total = contents.len() as u64;
+    let mut bytes_written = 0;
+    for chunk in contents.chunks(config().write_chunk_bytes.max(1)) {
+        file.write_all(chunk)?;
+        bytes_written += chunk.len() as u64;
+        let progress = FileWriteProgress { path: path.display().to_string(), bytes_written, total };
+        if let Err(err) = app_handle.emit("file-write-progress", progress) {
+            log_error!("Failed to emit file-write-progress event: {:?}", err);
+        }
+    }
+    Ok(())
+}
 
-    // Create the file
-    let mut file = File::create(&path).map_err(|e| e.to_string())?;
+// Add a command to save files using the standard Rust fs module
+#[tauri::command]
+async fn save_file(app_handle: AppHandle, path: String, contents: Vec<u8>) -> Result<(), String> {
+    log_line!("Saving file to: {}", path);
 
-    // Write the contents
-    file.write_all(&contents).map_err(|e| e.to_string())?;
+    write_with_progress(&app_handle, Path::new(&path), &contents).map_err(|e| e.to_string())?;
 
-    println!("File saved successfully");
+    log_line!("File saved successfully");
     Ok(())
 }
 
@@ -58,8 +115,230 @@ struct ProxyFetchResponse {
     body: String,
 }
 
+/// Headers that only apply to a single connection and must not be passed on
+/// after a proxy hop (RFC 7230 §6.1).
+const HOP_BY_HOP_HEADERS: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Collect upstream response headers for the renderer, dropping hop-by-hop
+/// headers (including any named in `Connection`) and, if `allowlist` is
+/// given, anything not on it.
+fn filter_proxy_headers(
+    headers: &reqwest::header::HeaderMap,
+    allowlist: Option<&[String]>,
+) -> Vec<(String, String)> {
+    let connection_listed: Vec<String> = headers
+        .get_all(reqwest::header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .collect();
+
+    headers
+        .iter()
+        .filter(|(k, _)| {
+            let name = k.as_str();
+            !HOP_BY_HOP_HEADERS.contains(&name) && !connection_listed.iter().any(|c| c == name)
+        })
+        .filter(|(k, _)| {
+            allowlist.is_none_or(|allowed| allowed.iter().any(|a| a.eq_ignore_ascii_case(k.as_str())))
+        })
+        .map(|(k, v)| (k.as_str().to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+/// Run an outbound request, abandoning it as soon as the app starts shutting
+/// down so a hung upstream can't delay exit.
+async fn cancel_on_shutdown<T>(request: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::select! {
+        biased;
+        _ = SHUTDOWN.cancelled() => Err("cancelled: app is shutting down".into()),
+        result = request => result,
+    }
+}
+
+/// Client settings shared by every outbound request made on the renderer's behalf.
+fn proxy_client_builder() -> reqwest::ClientBuilder {
+    Client::builder()
+        .user_agent("metanet-desktop/1.0 (+https://github.com/bsv-blockchain/metanet-desktop)")
+        .redirect(reqwest::redirect::Policy::limited(5))
+}
+
+/// Token bucket for one upstream host. Holds up to one second's worth of
+/// requests, refilled continuously.
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+static PROXY_BUCKETS: Lazy<DashMap<String, TokenBucket>> = Lazy::new(DashMap::new);
+/// Per-host overrides of `Config::proxy_rate_per_sec`.
+static PROXY_RATE_OVERRIDES: Lazy<DashMap<String, f64>> = Lazy::new(DashMap::new);
+
+/// Take a token for `host`, or fail with a 429-style error if it's been
+/// asked too often. A rate of 0 or less disables limiting.
+fn throttle_proxy_host(host: &str) -> Result<(), String> {
+    let host = host.to_ascii_lowercase();
+    let rate = PROXY_RATE_OVERRIDES.get(&host).map(|r| *r).unwrap_or_else(|| config().proxy_rate_per_sec);
+    if rate <= 0.0 {
+        return Ok(());
+    }
+    let capacity = rate.max(1.0);
+    let mut bucket = PROXY_BUCKETS
+        .entry(host.clone())
+        .or_insert_with(|| TokenBucket { tokens: capacity, refilled_at: Instant::now() });
+    let now = Instant::now();
+    bucket.tokens = (bucket.tokens + now.duration_since(bucket.refilled_at).as_secs_f64() * rate).min(capacity);
+    bucket.refilled_at = now;
+    if bucket.tokens < 1.0 {
+        return Err(format!("429: too many requests to {host}"));
+    }
+    bucket.tokens -= 1.0;
+    Ok(())
+}
+
+/// Set the request rate allowed to `host`, or with `None` go back to the
+/// default. Rates must be finite and positive.
+#[tauri::command]
+fn set_proxy_rate_limit(host: String, per_sec: Option<f64>) -> Result<(), String> {
+    let host = host.trim().to_ascii_lowercase();
+    match per_sec {
+        Some(rate) if !(rate.is_finite() && rate > 0.0) => {
+            return Err(format!("invalid rate {rate}: must be a positive number"));
+        }
+        Some(rate) => {
+            PROXY_RATE_OVERRIDES.insert(host.clone(), rate);
+        }
+        None => {
+            PROXY_RATE_OVERRIDES.remove(&host);
+        }
+    }
+    PROXY_BUCKETS.remove(&host);
+    Ok(())
+}
+
+/// Client certificates to present to particular hosts, as PEM (certificate
+/// chain followed by the private key), keyed by lowercase host name.
+static CLIENT_IDENTITIES: Lazy<DashMap<String, Vec<u8>>> = Lazy::new(DashMap::new);
+
+/// Redirects `proxy_fetch_manifest` follows unless told otherwise, and the
+/// most a caller may ask for.
+const DEFAULT_PROXY_REDIRECTS: usize = 5;
+const MAX_PROXY_REDIRECTS: usize = 10;
+
+/// Follow at most `max_hops` redirects, each re-checked before it is taken
+/// by `check_redirect_target`. Cross-host hops such as apex to `www` or onto
+/// a CDN are fine; a hop that fails the check aborts the whole chain.
+fn proxy_redirect_policy(max_hops: usize) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_hops {
+            return attempt.error(format!("too many redirects (max {max_hops})"));
+        }
+        match check_redirect_target(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(reason) => attempt.error(reason),
+        }
+    })
+}
+
+/// A redirect hop must stay on https and must not point at this machine or
+/// a private network, so a public manifest host can't bounce the proxy onto
+/// local services. Only literal addresses and `localhost` names are caught;
+/// names are not resolved here.
+fn check_redirect_target(next: &Url) -> Result<(), String> {
+    let hop = format!("{}://{}", next.scheme(), next.host_str().unwrap_or(""));
+    if next.scheme() != "https" {
+        return Err(format!("redirect to non-https {hop}"));
+    }
+    let local = match next.host() {
+        None => true,
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => is_local_ipv4(ip),
+        Some(url::Host::Ipv6(ip)) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_local_ipv4(ip),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // Unique local (fc00::/7) and link-local (fe80::/10).
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+            }
+        },
+    };
+    if local {
+        return Err(format!("redirect to private or loopback address {hop}"));
+    }
+    Ok(())
+}
+
+fn is_local_ipv4(ip: std::net::Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // Carrier-grade NAT (100.64.0.0/10).
+        || (a == 100 && (64..128).contains(&b))
+}
+
+/// Like `proxy_client_builder`, but presents the registered client
+/// certificate if `host` requires mutual TLS, and follows redirects per
+/// `redirect`.
+fn proxy_client_for_host(host: Option<&str>, redirect: reqwest::redirect::Policy) -> Result<Client, String> {
+    let mut builder = proxy_client_builder().redirect(redirect);
+    if let Some(pem) = host.and_then(|h| CLIENT_IDENTITIES.get(&h.to_ascii_lowercase())) {
+        let identity = reqwest::Identity::from_pem(&pem).map_err(|e| e.to_string())?;
+        builder = builder.identity(identity);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// Register (or, with an empty `pem`, remove) the client certificate to use
+/// for `host`. The PEM must contain both the certificate and its key.
+#[tauri::command]
+fn register_client_identity(host: String, pem: String) -> Result<(), String> {
+    let host = host.trim().to_ascii_lowercase();
+    if host.is_empty() {
+        return Err("host must not be empty".into());
+    }
+    if pem.trim().is_empty() {
+        CLIENT_IDENTITIES.remove(&host);
+        return Ok(());
+    }
+    reqwest::Identity::from_pem(pem.as_bytes()).map_err(|e| format!("invalid identity: {e}"))?;
+    CLIENT_IDENTITIES.insert(host, pem.into_bytes());
+    Ok(())
+}
+
+/// Decode a response body for `strict_utf8` callers, reporting where the
+/// first invalid byte sits instead of replacing it.
+fn strict_utf8_body(bytes: &[u8]) -> Result<String, String> {
+    std::str::from_utf8(bytes)
+        .map(str::to_string)
+        .map_err(|e| format!("response is not valid UTF-8 at byte {}", e.valid_up_to()))
+}
+
 #[tauri::command]
-async fn proxy_fetch_manifest(url: String) -> Result<ProxyFetchResponse, String> {
+async fn proxy_fetch_manifest(
+    url: String,
+    header_allowlist: Option<Vec<String>>,
+    strict_utf8: Option<bool>,
+    max_redirects: Option<usize>,
+) -> Result<ProxyFetchResponse, String> {
     let parsed = Url::parse(&url).map_err(|e| format!("invalid url: {e}"))?;
     if parsed.scheme() != "https" {
         return Err("only https scheme is allowed".into());
@@ -69,44 +348,656 @@ async fn proxy_fetch_manifest(url: String) -> Result<ProxyFetchResponse, String>
         return Err("only manifest.json paths are allowed".into());
     }
 
+    if let Some(host) = parsed.host_str() {
+        throttle_proxy_host(host)?;
+    }
+
     // Perform request
-    let client = Client::builder()
-        .user_agent("metanet-desktop/1.0 (+https://github.com/bsv-blockchain/metanet-desktop)")
-        .redirect(reqwest::redirect::Policy::limited(5))
+    let max_redirects = max_redirects.unwrap_or(DEFAULT_PROXY_REDIRECTS).min(MAX_PROXY_REDIRECTS);
+    let redirect = proxy_redirect_policy(max_redirects);
+    let client = proxy_client_for_host(parsed.host_str(), redirect)?;
+
+    cancel_on_shutdown(async move {
+        let resp = client
+            .get(parsed)
+            .header(reqwest::header::ACCEPT, "application/json, */*;q=0.8")
+            .send()
+            .await
+            .map_err(|e| match std::error::Error::source(&e) {
+                // Surface why a redirect was refused, not just that it was.
+                Some(reason) if e.is_redirect() => reason.to_string(),
+                _ => e.to_string(),
+            })?;
+
+        let status = resp.status().as_u16();
+        let headers_vec = filter_proxy_headers(resp.headers(), header_allowlist.as_deref());
+
+        // By default invalid UTF-8 is replaced; strict callers get an error instead.
+        let body = if strict_utf8.unwrap_or(false) {
+            strict_utf8_body(&resp.bytes().await.map_err(|e| e.to_string())?)?
+        } else {
+            resp.text().await.map_err(|e| e.to_string())?
+        };
+
+        Ok(ProxyFetchResponse { status, headers: headers_vec, body })
+    })
+    .await
+    .inspect_err(|e| record_error("proxy", format!("{url}: {e}")))
+}
+
+/// Reachability of a single host, as reported by `probe_proxy_hosts`.
+#[derive(Serialize, Clone)]
+struct HostProbe {
+    host: String,
+    reachable: bool,
+    latency_ms: Option<u64>,
+    error: Option<String>,
+}
+
+/// Turn a bare `host[:port]` into its `https://<host>/` root, rejecting
+/// anything carrying a scheme, credentials, path, query or fragment.
+fn proxy_host_url(host: &str) -> Result<Url, String> {
+    match Url::parse(&format!("https://{}/", host)) {
+        Ok(url)
+            if url.host_str().is_some()
+                && url.username().is_empty()
+                && url.password().is_none()
+                && url.path() == "/"
+                && url.query().is_none()
+                && url.fragment().is_none() =>
+        {
+            Ok(url)
+        }
+        _ => Err("invalid host".to_string()),
+    }
+}
+
+/// Issue a quick HEAD to `https://<host>/`. Any HTTP response, whatever its
+/// status, counts as reachable.
+async fn probe_host(client: &Client, host: &str) -> HostProbe {
+    let started = Instant::now();
+    let result = match proxy_host_url(host) {
+        Ok(url) => cancel_on_shutdown(async { client.head(url).send().await.map_err(|e| e.to_string()) }).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(_) => HostProbe {
+            host: host.to_string(),
+            reachable: true,
+            latency_ms: Some(started.elapsed().as_millis() as u64),
+            error: None,
+        },
+        Err(error) => HostProbe {
+            host: host.to_string(),
+            reachable: false,
+            latency_ms: None,
+            error: Some(error),
+        },
+    }
+}
+
+/// Probe each host concurrently with a short timeout. There is no host
+/// allowlist in this build, so the caller supplies the hosts to check.
+#[tauri::command]
+async fn probe_proxy_hosts(hosts: Vec<String>) -> Result<Vec<HostProbe>, String> {
+    let client = proxy_client_builder()
+        .timeout(Duration::from_secs(3))
         .build()
         .map_err(|e| e.to_string())?;
+    let handles: Vec<_> = hosts
+        .into_iter()
+        .map(|host| {
+            let client = client.clone();
+            tokio::spawn(async move { probe_host(&client, &host).await })
+        })
+        .collect();
 
-    let resp = client
-        .get(parsed)
-        .header(reqwest::header::ACCEPT, "application/json, */*;q=0.8")
-        .send()
-        .await
+    let mut probes = Vec::with_capacity(handles.len());
+    for handle in handles {
+        probes.push(handle.await.map_err(|e| e.to_string())?);
+    }
+    Ok(probes)
+}
+
+/// Check a host someone is about to allow for proxying: it must be a
+/// well-formed https host and answer a quick probe. Nothing is saved;
+/// adding the host is left to the caller.
+#[tauri::command]
+async fn test_proxy_host(host: String) -> Result<HostProbe, String> {
+    let client = proxy_client_builder()
+        .timeout(Duration::from_secs(3))
+        .build()
         .map_err(|e| e.to_string())?;
+    Ok(probe_host(&client, host.trim()).await)
+}
 
-    let status = resp.status().as_u16();
-    let mut headers_vec: Vec<(String, String)> = Vec::new();
-    for (k, v) in resp.headers().iter() {
-        headers_vec.push((
-            k.as_str().to_string(),
-            v.to_str().unwrap_or("").to_string(),
-        ));
+/// Environment variables reqwest and most tools consult for proxying.
+const PROXY_ENV_VARS: [&str; 8] = [
+    "HTTP_PROXY", "http_proxy", "HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy", "NO_PROXY", "no_proxy",
+];
+
+/// Short enough that the whole report comes back quickly.
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct DnsLookup {
+    host: String,
+    addresses: Vec<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct NetworkDiagnostics {
+    /// Proxy variables that are set, with any credentials removed.
+    proxy_env: BTreeMap<String, String>,
+    dns: Vec<DnsLookup>,
+    reachability: Vec<HostProbe>,
+}
+
+/// Strip `user:password@` from a proxy URL before reporting it.
+fn redact_proxy_url(value: &str) -> String {
+    match Url::parse(value) {
+        Ok(mut url) if !url.username().is_empty() || url.password().is_some() => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        _ => value.to_string(),
     }
+}
+
+async fn lookup_host(host: String) -> DnsLookup {
+    let result = match tokio::time::timeout(DNS_TIMEOUT, tokio::net::lookup_host((host.as_str(), 443))).await {
+        Ok(Ok(addrs)) => Ok(addrs.map(|a| a.ip().to_string()).collect()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    };
+    match result {
+        Ok(addresses) => DnsLookup { host, addresses, error: None },
+        Err(error) => DnsLookup { host, addresses: Vec::new(), error: Some(error) },
+    }
+}
+
+/// One report covering proxy settings, DNS and reachability for `hosts`,
+/// to help users work out why they can't connect.
+#[tauri::command]
+async fn get_network_diagnostics(hosts: Vec<String>) -> Result<NetworkDiagnostics, String> {
+    let proxy_env = PROXY_ENV_VARS
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (name.to_string(), redact_proxy_url(&value))))
+        .collect();
+
+    let lookups: Vec<_> = hosts.iter().cloned().map(|host| tokio::spawn(lookup_host(host))).collect();
+    let reachability = probe_proxy_hosts(hosts).await?;
+    let mut dns = Vec::with_capacity(lookups.len());
+    for lookup in lookups {
+        dns.push(lookup.await.map_err(|e| e.to_string())?);
+    }
+
+    Ok(NetworkDiagnostics { proxy_env, dns, reachability })
+}
+
+/// Queried for a reference time when the caller doesn't name a host.
+const CLOCK_REFERENCE_URL: &str = "https://github.com/";
+/// Skew beyond this gets a warning; TLS tends to break well before an hour.
+const CLOCK_SKEW_WARN_SECS: i64 = 300;
+
+#[derive(Serialize)]
+struct ClockSkew {
+    reference: String,
+    /// Local clock minus the reference clock; positive means we run ahead.
+    skew_secs: i64,
+    warning: Option<String>,
+}
+
+/// Compare the local clock with the `Date` header of an https host (by
+/// default `CLOCK_REFERENCE_URL`), so TLS failures caused by a wrong clock
+/// can be reported as such. The `Date` header only has one-second precision.
+#[tauri::command]
+async fn check_clock_skew(host: Option<String>) -> Result<ClockSkew, String> {
+    let url = match host {
+        Some(host) => proxy_host_url(host.trim())?,
+        None => Url::parse(CLOCK_REFERENCE_URL).map_err(|e| e.to_string())?,
+    };
+    let client = proxy_client_builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let sent_at = SystemTime::now();
+    let resp = cancel_on_shutdown(async { client.head(url.clone()).send().await.map_err(|e| e.to_string()) })
+        .await
+        .map_err(|e| format!("could not reach {url}: {e}; if this is a certificate error, check the system clock"))?;
+    // Assume the server stamped the response halfway through the round trip.
+    let local = sent_at + sent_at.elapsed().unwrap_or_default() / 2;
 
-    let body = resp.text().await.map_err(|e| e.to_string())?;
+    let date = resp
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| format!("{url} sent no Date header"))?;
+    let remote = httpdate::parse_http_date(date).map_err(|e| format!("invalid Date header {date:?}: {e}"))?;
 
-    Ok(ProxyFetchResponse { status, headers: headers_vec, body })
+    let skew_secs = match local.duration_since(remote) {
+        Ok(ahead) => ahead.as_secs() as i64,
+        Err(behind) => -(behind.duration().as_secs() as i64),
+    };
+    let warning = (skew_secs.abs() > CLOCK_SKEW_WARN_SECS).then(|| {
+        let direction = if skew_secs > 0 { "ahead" } else { "behind" };
+        format!(
+            "Your system clock is {} seconds {}. Secure connections may fail until it is corrected.",
+            skew_secs.abs(),
+            direction
+        )
+    });
+    Ok(ClockSkew { reference: url.to_string(), skew_secs, warning })
 }
 
 static MAIN_WINDOW_NAME: &str = "main";
 
+/// Runtime configuration. Defaults can be overridden by an imported config
+/// file and then by `MND_*` environment variables at startup, and some
+/// values can be changed later via commands. Fields missing from a saved
+/// or imported file take their defaults, so files from older builds load.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct Config {
+    /// Seconds of inactivity before the renderer is told to lock. 0 disables.
+    auto_lock_secs: u64,
+    /// Renderer round-trips slower than this are logged.
+    slow_request_ms: u64,
+    /// Seconds a client connection may sit idle before it is closed. 0 disables.
+    idle_connection_secs: u64,
+    /// Seconds a response is remembered for replay under its `Idempotency-Key`.
+    idempotency_ttl_secs: u64,
+    /// How long to wait for the renderer to answer a forwarded request.
+    renderer_timeout_ms: u64,
+    /// Upper bound for per-request `X-MND-Timeout-Ms` overrides.
+    max_renderer_timeout_ms: u64,
+    /// Incidental browser requests answered with 204 instead of being forwarded.
+    swallow_paths: Vec<String>,
+    /// Requests whose path and query exceed this many bytes get 414.
+    max_uri_bytes: usize,
+    /// How often stale pending-request entries are swept.
+    pending_sweep_secs: u64,
+    /// Default outbound proxy requests per second allowed to any one host.
+    proxy_rate_per_sec: f64,
+    /// Path-prefix routing table; unmatched paths go to the renderer.
+    routes: Vec<Route>,
+    /// Event the server emits to forward a request to the renderer.
+    request_event: String,
+    /// Event the renderer emits to answer a forwarded request.
+    response_event: String,
+    /// Path browsers' `EventSource` connects to for wallet notifications.
+    sse_path: String,
+    /// Serve built-in diagnostic endpoints such as `/debug/echo`.
+    debug_endpoints: bool,
+    /// Request bodies larger than this are spilled to a temp file rather
+    /// than sent inline in the event.
+    spill_body_bytes: usize,
+    /// Disable Nagle's algorithm on accepted connections. Small
+    /// request/response pairs over loopback otherwise pick up delay.
+    tcp_nodelay: bool,
+    /// TCP keepalive interval for accepted connections; 0 leaves it off.
+    tcp_keepalive_secs: u64,
+    /// Chunk size for file writes that report progress.
+    write_chunk_bytes: usize,
+    /// Ignore trailing slashes and case when matching built-in endpoints.
+    normalize_builtin_paths: bool,
+    /// Cap on a gzip/deflate request body once decompressed.
+    max_decompressed_bytes: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            auto_lock_secs: 0,
+            slow_request_ms: 1000,
+            idle_connection_secs: 60,
+            idempotency_ttl_secs: 300,
+            // Generous, since requests may wait on the user approving them.
+            renderer_timeout_ms: 300_000,
+            max_renderer_timeout_ms: 600_000,
+            swallow_paths: vec!["/favicon.ico".into(), "/robots.txt".into()],
+            max_uri_bytes: 8 * 1024,
+            pending_sweep_secs: 60,
+            proxy_rate_per_sec: 10.0,
+            routes: Vec::new(),
+            request_event: "http-request".into(),
+            response_event: "ts-response".into(),
+            sse_path: "/events".into(),
+            debug_endpoints: cfg!(debug_assertions),
+            spill_body_bytes: 1024 * 1024,
+            tcp_nodelay: true,
+            tcp_keepalive_secs: 0,
+            write_chunk_bytes: 1024 * 1024,
+            normalize_builtin_paths: true,
+            max_decompressed_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Config::with_env(Config::default())
+    }
+
+    /// `defaults` with any `MND_*` environment overrides applied.
+    fn with_env(defaults: Config) -> Self {
+        Config {
+            auto_lock_secs: env_or("MND_AUTO_LOCK_SECS", defaults.auto_lock_secs),
+            slow_request_ms: env_or("MND_SLOW_REQUEST_MS", defaults.slow_request_ms),
+            idle_connection_secs: env_or("MND_IDLE_CONNECTION_SECS", defaults.idle_connection_secs),
+            idempotency_ttl_secs: env_or("MND_IDEMPOTENCY_TTL_SECS", defaults.idempotency_ttl_secs),
+            renderer_timeout_ms: env_or("MND_RENDERER_TIMEOUT_MS", defaults.renderer_timeout_ms),
+            max_renderer_timeout_ms: env_or("MND_MAX_RENDERER_TIMEOUT_MS", defaults.max_renderer_timeout_ms),
+            swallow_paths: env_list("MND_SWALLOW_PATHS", defaults.swallow_paths),
+            max_uri_bytes: env_or("MND_MAX_URI_BYTES", defaults.max_uri_bytes),
+            pending_sweep_secs: env_or("MND_PENDING_SWEEP_SECS", defaults.pending_sweep_secs),
+            proxy_rate_per_sec: env_or("MND_PROXY_RATE_PER_SEC", defaults.proxy_rate_per_sec),
+            routes: std::env::var("MND_ROUTES").map(|v| parse_routes(&v)).unwrap_or(defaults.routes),
+            request_event: env_or("MND_REQUEST_EVENT", defaults.request_event),
+            response_event: env_or("MND_RESPONSE_EVENT", defaults.response_event),
+            sse_path: env_or("MND_SSE_PATH", defaults.sse_path),
+            debug_endpoints: env_or("MND_DEBUG_ENDPOINTS", defaults.debug_endpoints),
+            spill_body_bytes: env_or("MND_SPILL_BODY_BYTES", defaults.spill_body_bytes),
+            tcp_nodelay: env_or("MND_TCP_NODELAY", defaults.tcp_nodelay),
+            tcp_keepalive_secs: env_or("MND_TCP_KEEPALIVE_SECS", defaults.tcp_keepalive_secs),
+            write_chunk_bytes: env_or("MND_WRITE_CHUNK_BYTES", defaults.write_chunk_bytes),
+            normalize_builtin_paths: env_or("MND_NORMALIZE_BUILTIN_PATHS", defaults.normalize_builtin_paths),
+            max_decompressed_bytes: env_or("MND_MAX_DECOMPRESSED_BYTES", defaults.max_decompressed_bytes),
+        }
+    }
+}
+
+/// Parse a comma-separated environment variable, falling back to `default` if unset.
+fn env_list(name: &str, default: Vec<String>) -> Vec<String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        Err(_) => default,
+    }
+}
+
+/// Parse an environment variable, falling back to `default` if it is unset or invalid.
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            log_error!("Ignoring invalid value for {}: {:?}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+static CONFIG: Lazy<RwLock<Config>> = Lazy::new(|| RwLock::new(Config::from_env()));
+
+/// Snapshot of the current configuration.
+fn config() -> Config {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Address the HTTP server actually bound, recorded once at startup.
+static BOUND_ADDR: OnceCell<SocketAddr> = OnceCell::new();
+
+/// Port the HTTP server tries to listen on.
+const PREFERRED_PORT: u16 = 3321;
+
+/// Outcome of the server's startup bind, for telling the user when another
+/// instance already holds the port.
+#[derive(Serialize, Clone)]
+struct BindDiagnostics {
+    preferred_port: u16,
+    preferred_port_taken: bool,
+    bound_port: Option<u16>,
+    primary_instance: bool,
+}
+
+static BIND_DIAGNOSTICS: OnceCell<BindDiagnostics> = OnceCell::new();
+
+/// What happened when the server bound, or an error if it hasn't tried yet.
+#[tauri::command]
+fn get_bind_diagnostics() -> Result<BindDiagnostics, String> {
+    BIND_DIAGNOSTICS
+        .get()
+        .cloned()
+        .ok_or_else(|| "server has not attempted to bind yet".to_string())
+}
+
+/// The port the HTTP server is listening on, or an error if it hasn't bound
+/// yet. Clients that start after `server-listening` fired can ask here.
+#[tauri::command]
+fn get_server_port() -> Result<u16, String> {
+    BOUND_ADDR
+        .get()
+        .map(|addr| addr.port())
+        .ok_or_else(|| "server is not listening yet".to_string())
+}
+
+#[derive(Serialize)]
+struct EventNames {
+    request_event: String,
+    response_event: String,
+}
+
+/// The event names the renderer should listen on and reply with.
+#[tauri::command]
+fn get_event_names() -> EventNames {
+    let config = config();
+    EventNames { request_event: config.request_event, response_event: config.response_event }
+}
+
+/// A single resolved setting and whether it differs from the built-in default.
+#[derive(Serialize)]
+struct ConfigEntry {
+    value: serde_json::Value,
+    /// "default" or "override" (environment variable or command).
+    source: &'static str,
+}
+
+#[derive(Serialize)]
+struct EffectiveConfig {
+    bound_addr: Option<String>,
+    settings: BTreeMap<String, ConfigEntry>,
+}
+
+#[tauri::command]
+fn get_effective_config() -> Result<EffectiveConfig, String> {
+    let to_map = |config: &Config| match serde_json::to_value(config) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err("config did not serialize to an object".to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    let defaults = to_map(&Config::default())?;
+    let settings = to_map(&config())?
+        .into_iter()
+        .map(|(key, value)| {
+            let source = if defaults.get(&key) == Some(&value) { "default" } else { "override" };
+            (key, ConfigEntry { value, source })
+        })
+        .collect();
+    Ok(EffectiveConfig {
+        bound_addr: BOUND_ADDR.get().map(|addr| addr.to_string()),
+        settings,
+    })
+}
+
+/// File an imported config is persisted to, in the app config directory.
+const CONFIG_FILE: &str = "config.json";
+
+fn config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(CONFIG_FILE))
+}
+
+/// Apply a previously imported config at startup. Environment variables
+/// still win over it.
+fn load_saved_config(app_handle: &AppHandle) {
+    let Ok(path) = config_path(app_handle) else {
+        return;
+    };
+    if let Some(saved) = read_saved_config(&path) {
+        *CONFIG.write().unwrap() = Config::with_env(saved);
+    }
+}
+
+/// The config saved at `path`, if there is a readable, valid one.
+fn read_saved_config(path: &Path) -> Option<Config> {
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents)
+        .inspect_err(|e| log_error!("Ignoring invalid {}: {}", path.display(), e))
+        .ok()
+}
+
+/// The full current configuration, for moving settings to another machine.
+/// `Config` holds no keys or tokens, so nothing needs redacting.
+#[tauri::command]
+fn export_config() -> Result<serde_json::Value, String> {
+    serde_json::to_value(config()).map_err(|e| e.to_string())
+}
+
+/// Why one field of an imported config was rejected.
+#[derive(Serialize)]
+struct ConfigFieldError {
+    field: String,
+    message: String,
+}
+
+impl ConfigFieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        ConfigFieldError { field: field.to_string(), message: message.into() }
+    }
+}
+
+/// Checks the types can't express. Returns every problem, not just the first.
+fn validate_config(config: &Config) -> Vec<ConfigFieldError> {
+    let mut errors = Vec::new();
+    if config.renderer_timeout_ms == 0 {
+        errors.push(ConfigFieldError::new("renderer_timeout_ms", "must be greater than 0"));
+    }
+    if config.renderer_timeout_ms > config.max_renderer_timeout_ms {
+        errors.push(ConfigFieldError::new("renderer_timeout_ms", "must not exceed max_renderer_timeout_ms"));
+    }
+    if !config.proxy_rate_per_sec.is_finite() {
+        errors.push(ConfigFieldError::new("proxy_rate_per_sec", "must be a finite number"));
+    }
+    if config.write_chunk_bytes == 0 {
+        errors.push(ConfigFieldError::new("write_chunk_bytes", "must be greater than 0"));
+    }
+    if !config.sse_path.starts_with('/') {
+        errors.push(ConfigFieldError::new("sse_path", "must start with '/'"));
+    }
+    if config.swallow_paths.iter().any(|path| !path.starts_with('/')) {
+        errors.push(ConfigFieldError::new("swallow_paths", "every path must start with '/'"));
+    }
+    if config.routes.iter().any(|route| !route.prefix.starts_with('/')) {
+        errors.push(ConfigFieldError::new("routes", "every prefix must start with '/'"));
+    }
+    for (field, event) in [("request_event", &config.request_event), ("response_event", &config.response_event)] {
+        if event.trim().is_empty() {
+            errors.push(ConfigFieldError::new(field, "must not be empty"));
+        }
+    }
+    errors
+}
+
+/// Validate a config exported by `export_config` (or a subset of its
+/// fields, which are applied over the current config) and, if every field
+/// passes, apply and persist it. Nothing is applied when any field fails.
+/// As at startup, `MND_*` environment variables still win over it.
+/// Settings read only at startup, like the event names or socket options,
+/// take effect on the next launch.
+#[tauri::command]
+fn import_config(app_handle: AppHandle, json: String) -> Result<(), Vec<ConfigFieldError>> {
+    let provided: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&json).map_err(|e| vec![ConfigFieldError::new("", format!("invalid JSON object: {e}"))])?;
+    let serde_json::Value::Object(current) =
+        serde_json::to_value(config()).map_err(|e| vec![ConfigFieldError::new("", e.to_string())])?
+    else {
+        return Err(vec![ConfigFieldError::new("", "config did not serialize to an object")]);
+    };
+
+    // Check each field on its own so every bad one is reported.
+    let mut errors = Vec::new();
+    for (field, value) in &provided {
+        if !current.contains_key(field) {
+            errors.push(ConfigFieldError::new(field, "unknown setting"));
+            continue;
+        }
+        let mut candidate = current.clone();
+        candidate.insert(field.clone(), value.clone());
+        if let Err(e) = serde_json::from_value::<Config>(serde_json::Value::Object(candidate)) {
+            errors.push(ConfigFieldError::new(field, e.to_string()));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut merged = current;
+    merged.extend(provided);
+    let imported: Config =
+        serde_json::from_value(serde_json::Value::Object(merged)).map_err(|e| vec![ConfigFieldError::new("", e.to_string())])?;
+    let errors = validate_config(&imported);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let path = config_path(&app_handle).map_err(|e| vec![ConfigFieldError::new("", e)])?;
+    write_json_atomic(&path, &imported).map_err(|e| vec![ConfigFieldError::new("", e)])?;
+    let effective = Config::with_env(imported);
+    *CONFIG.write().unwrap() = effective.clone();
+    if let Err(err) = app_handle.emit("config-imported", effective) {
+        log_error!("Failed to emit config-imported event: {:?}", err);
+    }
+    Ok(())
+}
+
+/// Version of the `http-request` payload shape. Bump whenever a field is
+/// added, removed or changes meaning, so the renderer can branch on it.
+///
+/// 1: method, path, headers, body, request_id
+/// 2: adds remote_addr and protocol_version
+/// 3: adds http_version
+/// 4: adds body_file; large bodies arrive there instead of in body
+const PROTOCOL_VERSION: u32 = 4;
+
 /// Payload sent from Rust to the frontend for each HTTP request.
 #[derive(Serialize)]
 struct HttpRequestEvent {
+    /// See `PROTOCOL_VERSION`.
+    protocol_version: u32,
+    /// HTTP method, e.g. "POST".
     method: String,
+    /// Request target as received, including any query string.
     path: String,
+    /// Header name/value pairs in the order received.
     headers: Vec<(String, String)>,
+    /// Request body, lossily decoded as UTF-8.
     body: String,
+    /// Echo this back in the matching `ts-response`.
     request_id: u64,
+    /// IP address of the connecting client.
+    remote_addr: String,
+    /// Protocol the client spoke, e.g. "HTTP/1.1".
+    http_version: String,
+    /// Set when the body was too large to send inline: a file the renderer
+    /// can page through with `read_file_chunk`. `body` is empty then. The
+    /// file is deleted once the response is sent.
+    body_file: Option<String>,
+}
+
+/// Set once the renderer has registered its `http-request` listener. Until
+/// then requests are answered with 503 instead of being forwarded into the void.
+static RENDERER_READY: AtomicBool = AtomicBool::new(false);
+
+/// Lets the renderer check the payload version before any request arrives.
+#[tauri::command]
+fn get_protocol_version() -> u32 {
+    PROTOCOL_VERSION
 }
 
 /// Expected payload sent back from the frontend.
@@ -115,259 +1006,3950 @@ struct TsResponse {
     request_id: u64,
     status: u16,
     body: String,
+    /// Extra response headers. Repeated names (e.g. `Set-Cookie`) are all sent.
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    /// Custom reason phrase, e.g. "Awesome" for `HTTP/1.1 200 Awesome`.
+    /// Only HTTP/1 carries one; HTTP/2 clients never see it.
+    #[serde(default)]
+    status_text: Option<String>,
+    /// Serve identical requests from cache for this long instead of
+    /// forwarding them again.
+    #[serde(default)]
+    cache_ttl_ms: Option<u64>,
 }
 
-/// A type alias for our concurrent map of pending responses.
-type PendingMap = DashMap<u64, oneshot::Sender<TsResponse>>;
+/// Running totals for requests answered by the renderer.
+#[derive(Default)]
+struct RequestMetrics {
+    requests: AtomicU64,
+    request_bytes: AtomicU64,
+    response_bytes: AtomicU64,
+    round_trip_ms: AtomicU64,
+    slow_requests: AtomicU64,
+}
 
-#[cfg(target_os = "macos")]
-use once_cell::sync::Lazy;
-/// -----
-/// Tauri COMMANDS for focus management
-/// -----
+static REQUEST_METRICS: Lazy<RequestMetrics> = Lazy::new(RequestMetrics::default);
 
-#[cfg(target_os = "macos")]
-use std::sync::Mutex;
+/// A completed request, kept for support bundles. Only the path is stored,
+/// never the query string or bodies.
+#[derive(Serialize, Clone)]
+struct RequestRecord {
+    at_unix_ms: u64,
+    method: String,
+    path: String,
+    status: u16,
+    duration_ms: u64,
+}
 
-#[cfg(target_os = "macos")]
-static PREV_BUNDLE_ID: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+const RECENT_REQUESTS_CAPACITY: usize = 100;
 
-#[tauri::command]
-fn is_focused(window: Window) -> bool {
-    match window.is_focused() {
-        Ok(focused) => focused,
-        Err(_) => false,
+static RECENT_REQUESTS: Lazy<Mutex<VecDeque<RequestRecord>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_REQUESTS_CAPACITY)));
+
+/// A failure worth showing in the in-app status view.
+#[derive(Serialize, Clone)]
+struct RecentError {
+    at_unix_ms: u64,
+    /// Broad source, e.g. "bind", "renderer", "timeout", "proxy".
+    category: &'static str,
+    message: String,
+}
+
+const RECENT_ERRORS_CAPACITY: usize = 50;
+
+static RECENT_ERRORS: Lazy<Mutex<VecDeque<RecentError>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RECENT_ERRORS_CAPACITY)));
+
+/// The most recent log lines, oldest first, each with its Unix ms time.
+const LOG_LINES_CAPACITY: usize = 500;
+
+static LOG_LINES: Lazy<Mutex<VecDeque<(u64, String)>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(LOG_LINES_CAPACITY)));
+
+fn record_log_line(line: String) {
+    // Logging must never panic, even after another thread poisoned the lock.
+    let mut lines = LOG_LINES.lock().unwrap_or_else(|e| e.into_inner());
+    if lines.len() == LOG_LINES_CAPACITY {
+        lines.pop_front();
+    }
+    lines.push_back((unix_millis(), line));
+}
+
+/// Remember an error for `get_recent_errors`. Callers still log it themselves.
+fn record_error(category: &'static str, message: String) {
+    let mut recent = RECENT_ERRORS.lock().unwrap();
+    if recent.len() == RECENT_ERRORS_CAPACITY {
+        recent.pop_front();
     }
+    recent.push_back(RecentError { at_unix_ms: unix_millis(), category, message });
 }
 
+/// Recent errors, oldest first.
 #[tauri::command]
-fn request_focus(window: Window) {
-    #[cfg(target_os = "macos")]
-    {
-        // Make window visible first - critical for macOS
-        use std::process::Command;
+fn get_recent_errors() -> Vec<RecentError> {
+    RECENT_ERRORS.lock().unwrap().iter().cloned().collect()
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Record a completed renderer round-trip, logging it if it was slow.
+fn record_request_metrics(
+    method: &str,
+    path: &str,
+    status: u16,
+    request_bytes: usize,
+    response_bytes: usize,
+    elapsed: Duration,
+) {
+    let elapsed_ms = elapsed.as_millis() as u64;
+    {
+        let mut recent = RECENT_REQUESTS.lock().unwrap();
+        if recent.len() == RECENT_REQUESTS_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(RequestRecord {
+            at_unix_ms: unix_millis(),
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            duration_ms: elapsed_ms,
+        });
+    }
+    let metrics = &*REQUEST_METRICS;
+    metrics.requests.fetch_add(1, Ordering::Relaxed);
+    metrics.request_bytes.fetch_add(request_bytes as u64, Ordering::Relaxed);
+    metrics.response_bytes.fetch_add(response_bytes as u64, Ordering::Relaxed);
+    metrics.round_trip_ms.fetch_add(elapsed_ms, Ordering::Relaxed);
+    LATENCY_HISTOGRAM.record(elapsed_ms);
+
+    if elapsed_ms >= config().slow_request_ms {
+        metrics.slow_requests.fetch_add(1, Ordering::Relaxed);
+        log_error!(
+            "WARN slow request: {} {} took {}ms (request {} bytes, response {} bytes)",
+            method, path, elapsed_ms, request_bytes, response_bytes
+        );
+    } else if VERBOSE_LOGGING.load(Ordering::Relaxed) {
+        log_line!("{} {} -> {} in {}ms", method, path, status, elapsed_ms);
+    }
+}
+
+/// When set, every completed request is logged, not just slow ones.
+static VERBOSE_LOGGING: AtomicBool = AtomicBool::new(false);
+
+#[tauri::command]
+fn set_verbose_logging(enabled: bool) {
+    VERBOSE_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+#[derive(Serialize)]
+struct RequestMetricsSnapshot {
+    requests: u64,
+    request_bytes: u64,
+    response_bytes: u64,
+    avg_round_trip_ms: u64,
+    slow_requests: u64,
+}
+
+#[tauri::command]
+fn get_request_metrics() -> RequestMetricsSnapshot {
+    let metrics = &*REQUEST_METRICS;
+    let requests = metrics.requests.load(Ordering::Relaxed);
+    RequestMetricsSnapshot {
+        requests,
+        request_bytes: metrics.request_bytes.load(Ordering::Relaxed),
+        response_bytes: metrics.response_bytes.load(Ordering::Relaxed),
+        avg_round_trip_ms: metrics.round_trip_ms.load(Ordering::Relaxed) / requests.max(1),
+        slow_requests: metrics.slow_requests.load(Ordering::Relaxed),
+    }
+}
+
+/// Upper bounds (inclusive, ms) of the latency histogram buckets. Anything
+/// slower lands in a final overflow bucket.
+const LATENCY_BUCKETS_MS: [u64; 12] = [5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000];
+
+/// Renderer round-trip counts per bucket, updated lock-free per request.
+struct LatencyHistogram {
+    counts: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+impl LatencyHistogram {
+    fn record(&self, elapsed_ms: u64) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|le| elapsed_ms <= *le)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+static LATENCY_HISTOGRAM: LatencyHistogram = LatencyHistogram {
+    counts: [const { AtomicU64::new(0) }; LATENCY_BUCKETS_MS.len() + 1],
+};
+
+#[derive(Serialize)]
+struct LatencyBucket {
+    /// Upper bound in ms; `None` for the overflow bucket.
+    le_ms: Option<u64>,
+    count: u64,
+}
+
+/// Renderer round-trip latencies since startup (or the last reset), bucketed
+/// for charting.
+#[tauri::command]
+fn get_latency_histogram() -> Vec<LatencyBucket> {
+    LATENCY_HISTOGRAM
+        .counts
+        .iter()
+        .enumerate()
+        .map(|(i, count)| LatencyBucket {
+            le_ms: LATENCY_BUCKETS_MS.get(i).copied(),
+            count: count.load(Ordering::Relaxed),
+        })
+        .collect()
+}
+
+#[tauri::command]
+fn reset_latency_histogram() {
+    for count in &LATENCY_HISTOGRAM.counts {
+        count.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A 414 for a request whose path and query exceed `max_bytes`, keeping
+/// pathological URLs out of the event payload.
+fn uri_too_long(uri: &hyper::Uri, max_bytes: usize) -> Option<Response<Body>> {
+    let len = uri.path_and_query().map_or(0, |pq| pq.as_str().len());
+    (len > max_bytes)
+        .then(|| json_error_response(StatusCode::URI_TOO_LONG, serde_json::json!({ "error": "uri-too-long" })))
+}
+
+/// Whether a request's `Host` header names this server. Browsers always send
+/// it, so rejecting foreign hosts defeats DNS-rebinding attacks where a
+/// malicious site resolves its own name to 127.0.0.1.
+fn is_allowed_host(host: Option<&str>, bound: SocketAddr) -> bool {
+    let Some(host) = host else {
+        // Only non-browser HTTP/1.0 clients omit Host.
+        return true;
+    };
+    let host = host.trim().to_ascii_lowercase();
+    let port = bound.port();
+    host == bound.to_string()
+        || host == format!("127.0.0.1:{}", port)
+        || host == format!("localhost:{}", port)
+}
+
+/// Renderer-registered timeouts in ms, keyed by exact path or by a prefix
+/// ending in `*`.
+static PATH_TIMEOUTS: Lazy<DashMap<String, u64>> = Lazy::new(DashMap::new);
+
+/// Register (or with `None`, remove) the renderer timeout for requests to
+/// `path_pattern`, e.g. `/createAction` or `/prove*`. Returns the timeout
+/// actually applied after clamping.
+#[tauri::command]
+fn set_path_timeout(path_pattern: String, timeout_ms: Option<u64>) -> Result<Option<u64>, String> {
+    if !path_pattern.starts_with('/') {
+        return Err("path pattern must start with '/'".into());
+    }
+    match timeout_ms.filter(|ms| *ms > 0) {
+        Some(ms) => {
+            let ms = ms.min(config().max_renderer_timeout_ms);
+            PATH_TIMEOUTS.insert(path_pattern, ms);
+            Ok(Some(ms))
+        }
+        None => {
+            PATH_TIMEOUTS.remove(&path_pattern);
+            Ok(None)
+        }
+    }
+}
+
+/// The registered timeout for `path`: an exact match, else the longest
+/// matching `*` prefix.
+fn path_timeout(path: &str) -> Option<u64> {
+    if let Some(ms) = PATH_TIMEOUTS.get(path) {
+        return Some(*ms);
+    }
+    PATH_TIMEOUTS
+        .iter()
+        .filter_map(|entry| {
+            let prefix = entry.key().strip_suffix('*')?;
+            path.starts_with(prefix).then(|| (prefix.len(), *entry.value()))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, ms)| ms)
+}
+
+/// Limits for requests carrying a given `Origin`. Unset fields fall back to
+/// the global defaults.
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct OriginPolicy {
+    max_concurrency: Option<u32>,
+    timeout_ms: Option<u64>,
+}
+
+/// A policy plus the semaphore enforcing its concurrency limit.
+struct OriginLimits {
+    policy: OriginPolicy,
+    permits: Option<Arc<tokio::sync::Semaphore>>,
+}
+
+/// Per-origin policies keyed by normalized origin.
+static ORIGIN_POLICIES: Lazy<DashMap<String, OriginLimits>> = Lazy::new(DashMap::new);
+
+/// Settings namespace the policies persist under.
+const ORIGIN_POLICY_NAMESPACE: &str = "origin-policies";
+
+/// Origins compare case-insensitively and without a trailing slash.
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+fn install_origin_policy(origin: String, policy: OriginPolicy) {
+    let permits = policy
+        .max_concurrency
+        .filter(|n| *n > 0)
+        .map(|n| Arc::new(tokio::sync::Semaphore::new(n as usize)));
+    ORIGIN_POLICIES.insert(origin, OriginLimits { policy, permits });
+}
+
+/// Load saved origin policies into memory; called once at startup.
+fn load_origin_policies(app_handle: &AppHandle) {
+    let saved = with_settings(app_handle, |settings, _| {
+        Ok(settings.get(ORIGIN_POLICY_NAMESPACE).cloned().unwrap_or_default())
+    });
+    for (origin, json) in saved.unwrap_or_default() {
+        match serde_json::from_str(&json) {
+            Ok(policy) => install_origin_policy(origin, policy),
+            Err(e) => log_error!("Ignoring saved policy for {}: {}", origin, e),
+        }
+    }
+}
+
+/// Set (or with `None`, clear) the policy for `origin`, e.g.
+/// `https://app.example`, and persist it. Requests already in flight keep
+/// the limits they started under.
+#[tauri::command]
+fn set_origin_policy(app_handle: AppHandle, origin: String, policy: Option<OriginPolicy>) -> Result<(), String> {
+    let origin = normalize_origin(&origin);
+    if origin.is_empty() {
+        return Err("origin must not be empty".into());
+    }
+    let encoded = policy.as_ref().map(serde_json::to_string).transpose().map_err(|e| e.to_string())?;
+    kv_set(app_handle, ORIGIN_POLICY_NAMESPACE.to_string(), origin.clone(), encoded)?;
+    match policy {
+        Some(policy) => install_origin_policy(origin, policy),
+        None => {
+            ORIGIN_POLICIES.remove(&origin);
+        }
+    }
+    Ok(())
+}
+
+/// Every configured origin policy.
+#[tauri::command]
+fn get_origin_policies() -> BTreeMap<String, OriginPolicy> {
+    ORIGIN_POLICIES
+        .iter()
+        .map(|entry| (entry.key().clone(), entry.policy.clone()))
+        .collect()
+}
+
+/// The normalized `Origin` of a request, if it sent one.
+fn request_origin(headers: &hyper::HeaderMap) -> Option<String> {
+    headers
+        .get(hyper::header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(normalize_origin)
+}
+
+/// Outcome of checking a request against its origin's concurrency limit.
+enum OriginAdmission {
+    /// No limit applies, or a slot was taken; hold the permit until done.
+    Admitted(Option<tokio::sync::OwnedSemaphorePermit>),
+    /// The origin already has its maximum number of requests in flight.
+    Busy,
+}
+
+fn admit_origin(headers: &hyper::HeaderMap) -> OriginAdmission {
+    let permits = request_origin(headers)
+        .and_then(|origin| ORIGIN_POLICIES.get(&origin).and_then(|limits| limits.permits.clone()));
+    match permits {
+        None => OriginAdmission::Admitted(None),
+        Some(permits) => match permits.try_acquire_owned() {
+            Ok(permit) => OriginAdmission::Admitted(Some(permit)),
+            Err(_) => OriginAdmission::Busy,
+        },
+    }
+}
+
+/// How long to wait for the renderer: the client's `X-MND-Timeout-Ms` if it is
+/// a positive integer, else any timeout registered for the path, else the
+/// origin's policy timeout, else the default. Always clamped to the
+/// configured maximum.
+fn renderer_timeout(path: &str, headers: &hyper::HeaderMap) -> Duration {
+    let config = config();
+    let requested = headers
+        .get("x-mnd-timeout-ms")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|ms| *ms > 0);
+    let ms = requested
+        .or_else(|| path_timeout(path))
+        .or_else(|| {
+            let origin = request_origin(headers)?;
+            ORIGIN_POLICIES.get(&origin)?.policy.timeout_ms.filter(|ms| *ms > 0)
+        })
+        .unwrap_or(config.renderer_timeout_ms)
+        .min(config.max_renderer_timeout_ms);
+    Duration::from_millis(ms)
+}
+
+/// Answer a CORS preflight. Preflights never carry credentials, so this
+/// stays ahead of any check on the actual request.
+fn preflight_response() -> Response<Body> {
+    let mut res = Response::new(Body::empty());
+    add_cors_headers(&mut res);
+    res
+}
+
+/// Append the permissive CORS headers every local server response carries.
+fn add_cors_headers(res: &mut Response<Body>) {
+    let headers = res.headers_mut();
+    headers.insert("Access-Control-Allow-Origin", "*".parse().unwrap());
+    // The `*` wildcard never covers Authorization, so it's listed explicitly.
+    headers.insert("Access-Control-Allow-Headers", "*, Authorization".parse().unwrap());
+    headers.insert("Access-Control-Allow-Methods", "*".parse().unwrap());
+    headers.insert("Access-Control-Expose-Headers", "*".parse().unwrap());
+    headers.insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
+}
+
+/// Key used to match a path against the built-in endpoints (swallowed
+/// paths, registered endpoints, the event stream, debug echo). With
+/// `Config::normalize_builtin_paths`, trailing slashes are dropped and case
+/// is folded, so `/Favicon.ico/` is handled like `/favicon.ico`.
+fn builtin_path_key(path: &str) -> String {
+    normalized_path_key(path, config().normalize_builtin_paths)
+}
+
+fn normalized_path_key(path: &str, normalize: bool) -> String {
+    if !normalize {
+        return path.to_string();
+    }
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        trimmed.to_ascii_lowercase()
+    }
+}
+
+/// What a runtime-registered built-in endpoint answers with, as sent by the
+/// frontend.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum EndpointSpec {
+    /// A fixed response.
+    Static {
+        status: u16,
+        #[serde(default)]
+        headers: Vec<(String, String)>,
+        body: String,
+    },
+    /// A small JSON document computed per request.
+    Computed { value: ComputedValue },
+}
+
+/// Values a computed endpoint can report, none of which need the renderer.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ComputedValue {
+    Version,
+    Uptime,
+    Time,
+    ProtocolVersion,
+}
+
+/// A response served straight from the server, without a round-trip.
+enum BuiltinEndpoint {
+    Static { status: StatusCode, headers: Vec<(String, String)>, body: String },
+    Version(String),
+    Uptime(Instant),
+    Time,
+    ProtocolVersion,
+}
+
+impl BuiltinEndpoint {
+    fn respond(&self) -> Response<Body> {
+        let value = match self {
+            BuiltinEndpoint::Static { status, headers, body } => {
+                let mut res = Response::new(Body::from(body.clone()));
+                *res.status_mut() = *status;
+                add_renderer_headers(&mut res, headers);
+                add_cors_headers(&mut res);
+                return res;
+            }
+            BuiltinEndpoint::Version(version) => serde_json::json!({ "version": version }),
+            BuiltinEndpoint::Uptime(started) => serde_json::json!({ "uptime_secs": started.elapsed().as_secs() }),
+            BuiltinEndpoint::Time => serde_json::json!({ "unix_ms": unix_millis() }),
+            BuiltinEndpoint::ProtocolVersion => serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+        };
+        let mut res = Response::new(Body::from(value.to_string()));
+        res.headers_mut().insert(hyper::header::CONTENT_TYPE, "application/json".parse().unwrap());
+        add_cors_headers(&mut res);
+        res
+    }
+}
+
+/// Runtime-registered built-in endpoints keyed by `builtin_path_key` of the
+/// request path.
+static BUILTIN_ENDPOINTS: Lazy<DashMap<String, BuiltinEndpoint>> = Lazy::new(DashMap::new);
+
+/// Serve `path` directly from the server according to `spec`, replacing any
+/// endpoint already registered there.
+#[tauri::command]
+fn register_builtin_endpoint(
+    app_handle: AppHandle,
+    start_time: State<'_, StartTime>,
+    path: String,
+    spec: EndpointSpec,
+) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err("path must start with '/'".into());
+    }
+    let endpoint = match spec {
+        EndpointSpec::Static { status, headers, body } => {
+            let status = StatusCode::from_u16(status).map_err(|e| e.to_string())?;
+            for (name, value) in &headers {
+                hyper::header::HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("header {name:?}: {e}"))?;
+                hyper::header::HeaderValue::from_str(value).map_err(|e| format!("header {name:?}: {e}"))?;
+            }
+            BuiltinEndpoint::Static { status, headers, body }
+        }
+        EndpointSpec::Computed { value: ComputedValue::Version } => {
+            BuiltinEndpoint::Version(app_handle.package_info().version.to_string())
+        }
+        EndpointSpec::Computed { value: ComputedValue::Uptime } => BuiltinEndpoint::Uptime(start_time.instant),
+        EndpointSpec::Computed { value: ComputedValue::Time } => BuiltinEndpoint::Time,
+        EndpointSpec::Computed { value: ComputedValue::ProtocolVersion } => BuiltinEndpoint::ProtocolVersion,
+    };
+    BUILTIN_ENDPOINTS.insert(builtin_path_key(&path), endpoint);
+    Ok(())
+}
+
+/// Remove a registered built-in endpoint; returns whether one existed.
+#[tauri::command]
+fn unregister_builtin_endpoint(path: String) -> bool {
+    BUILTIN_ENDPOINTS.remove(&builtin_path_key(&path)).is_some()
+}
+
+/// Shorthand for a static built-in endpoint with just a content type.
+#[tauri::command]
+fn register_fast_path(path: String, status: u16, body: String, content_type: String) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err("path must start with '/'".into());
+    }
+    let status = StatusCode::from_u16(status).map_err(|e| e.to_string())?;
+    hyper::header::HeaderValue::from_str(&content_type).map_err(|e| e.to_string())?;
+    let headers = vec![("content-type".to_string(), content_type)];
+    BUILTIN_ENDPOINTS.insert(builtin_path_key(&path), BuiltinEndpoint::Static { status, headers, body });
+    Ok(())
+}
+
+/// Remove a fast path; returns whether one was registered.
+#[tauri::command]
+fn unregister_fast_path(path: String) -> bool {
+    unregister_builtin_endpoint(path)
+}
+
+/// The status a renderer answer is sent with, or `None` when it is outside
+/// 100-599. Hyper itself accepts up to 999.
+fn renderer_status(status: u16) -> Option<StatusCode> {
+    StatusCode::from_u16(status).ok().filter(|_| status < 600)
+}
+
+/// Build the client response for a renderer answer: status, reason phrase
+/// and renderer headers. CORS headers are left to the caller.
+fn renderer_response(
+    status: StatusCode,
+    body: String,
+    headers: &[(String, String)],
+    status_text: Option<&str>,
+) -> Response<Body> {
+    let mut res = Response::new(Body::from(body));
+    *res.status_mut() = status;
+    if let Some(text) = status_text {
+        match hyper::ext::ReasonPhrase::try_from(text.as_bytes()) {
+            Ok(reason) => {
+                res.extensions_mut().insert(reason);
+            }
+            Err(_) => log_error!("Ignoring invalid status_text from renderer: {:?}", text),
+        }
+    }
+    add_renderer_headers(&mut res, headers);
+    res
+}
+
+/// Append headers supplied by the renderer, skipping ones Hyper manages
+/// itself. Appends rather than inserts so repeated `Set-Cookie` survive.
+fn add_renderer_headers(res: &mut Response<Body>, headers: &[(String, String)]) {
+    for (name, value) in headers {
+        let Ok(name) = hyper::header::HeaderName::from_bytes(name.as_bytes()) else {
+            log_error!("Ignoring invalid response header name from renderer: {:?}", name);
+            continue;
+        };
+        if HOP_BY_HOP_HEADERS.contains(&name.as_str()) || name == hyper::header::CONTENT_LENGTH {
+            continue;
+        }
+        match hyper::header::HeaderValue::from_str(value) {
+            Ok(value) => {
+                res.headers_mut().append(name, value);
+            }
+            Err(_) => log_error!("Ignoring invalid value for response header {}", name),
+        }
+    }
+}
+
+/// An accepted client connection that reports end-of-stream once it has sat
+/// idle (no request in flight and no I/O) for longer than `timeout`, which
+/// makes Hyper close it. Guards against dead-but-open sockets from crashed
+/// dApps piling up.
+struct IdleTimeoutStream {
+    inner: AddrStream,
+    in_flight: Arc<AtomicUsize>,
+    timeout: Duration,
+    deadline: Pin<Box<tokio::time::Sleep>>,
+}
+
+impl IdleTimeoutStream {
+    fn new(inner: AddrStream, timeout: Duration) -> Self {
+        IdleTimeoutStream {
+            inner,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            timeout,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+
+    fn remote_addr(&self) -> SocketAddr {
+        self.inner.remote_addr()
+    }
+
+    /// Counter the service bumps while a request on this connection is in flight.
+    fn in_flight(&self) -> Arc<AtomicUsize> {
+        self.in_flight.clone()
+    }
+
+    fn reset_deadline(&mut self) {
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        self.deadline.as_mut().reset(deadline);
+    }
+}
+
+impl AsyncRead for IdleTimeoutStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let filled = buf.filled().len();
+        if let Poll::Ready(result) = Pin::new(&mut self.inner).poll_read(cx, buf) {
+            if buf.filled().len() > filled {
+                self.reset_deadline();
+            }
+            return Poll::Ready(result);
+        }
+        if self.timeout.is_zero() || self.in_flight.load(Ordering::Relaxed) > 0 {
+            return Poll::Pending;
+        }
+        if self.deadline.as_mut().poll(cx).is_ready() {
+            log_error!("Closing idle connection from {}", self.remote_addr());
+            return Poll::Ready(Ok(()));
+        }
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for IdleTimeoutStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = result {
+            if n > 0 {
+                self.reset_deadline();
+            }
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Marks a request as in flight on its connection for as long as it lives.
+struct InFlightGuard(Arc<AtomicUsize>);
+
+impl InFlightGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard(counter)
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// State of a POST carrying an `Idempotency-Key`.
+enum IdempotencyEntry {
+    /// Forwarded to the renderer but not yet answered.
+    InFlight { since: Instant },
+    /// Answered; retries replay this response instead of re-forwarding.
+    Completed {
+        status: StatusCode,
+        body: String,
+        headers: Vec<(String, String)>,
+        status_text: Option<String>,
+        at: Instant,
+    },
+}
+
+impl IdempotencyEntry {
+    fn is_expired(&self, ttl: Duration) -> bool {
+        match self {
+            IdempotencyEntry::InFlight { since } => since.elapsed() > ttl,
+            IdempotencyEntry::Completed { at, .. } => at.elapsed() > ttl,
+        }
+    }
+}
+
+/// Idempotency entries keyed by `idempotency_cache_key`.
+static IDEMPOTENCY_CACHE: Lazy<DashMap<String, IdempotencyEntry>> = Lazy::new(DashMap::new);
+/// Maps forwarded request ids to their idempotency cache key, so the
+/// response listener can record the result even if the client gave up.
+static IDEMPOTENT_REQUESTS: Lazy<DashMap<u64, String>> = Lazy::new(DashMap::new);
+
+/// Key an idempotent request by its `Origin`, path and `Idempotency-Key`,
+/// so one site can never replay another's response.
+fn idempotency_cache_key(headers: &hyper::HeaderMap, path: &str, key: &str) -> String {
+    let origin = headers
+        .get(hyper::header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    format!("{} {} {}", origin, path, key)
+}
+
+/// Store a renderer response for a request that carried an idempotency key.
+fn complete_idempotent_request(ts_response: &TsResponse) {
+    // Invalid statuses are answered with 502; let a retry try again.
+    let Some(status) = renderer_status(ts_response.status) else {
+        abandon_idempotent_request(ts_response.request_id);
+        return;
+    };
+    if let Some((_, key)) = IDEMPOTENT_REQUESTS.remove(&ts_response.request_id) {
+        IDEMPOTENCY_CACHE.insert(
+            key,
+            IdempotencyEntry::Completed {
+                status,
+                body: ts_response.body.clone(),
+                headers: ts_response.headers.clone(),
+                status_text: ts_response.status_text.clone(),
+                at: Instant::now(),
+            },
+        );
+    }
+}
+
+/// Forget an idempotent request the renderer never received, so a retry
+/// can be forwarded.
+fn abandon_idempotent_request(request_id: u64) {
+    if let Some((_, key)) = IDEMPOTENT_REQUESTS.remove(&request_id) {
+        IDEMPOTENCY_CACHE.remove(&key);
+    }
+}
+
+/// Drop remembered idempotent responses. In-flight entries are kept so a
+/// retry still sees `request-in-progress`.
+fn clear_idempotency_cache() -> usize {
+    let before = IDEMPOTENCY_CACHE.len();
+    IDEMPOTENCY_CACHE.retain(|_, entry| matches!(entry, IdempotencyEntry::InFlight { .. }));
+    before - IDEMPOTENCY_CACHE.len()
+}
+
+/// Longest a renderer may ask for a response to be cached, and the most
+/// responses kept at once.
+const RESPONSE_CACHE_MAX_TTL: Duration = Duration::from_secs(3600);
+const RESPONSE_CACHE_MAX_ENTRIES: usize = 512;
+
+/// A renderer response cached at its `cache_ttl_ms` hint.
+struct CachedResponse {
+    status: StatusCode,
+    body: String,
+    headers: Vec<(String, String)>,
+    expires_at: Instant,
+}
+
+/// Cached responses keyed by `response_cache_key`.
+static RESPONSE_CACHE: Lazy<DashMap<u64, CachedResponse>> = Lazy::new(DashMap::new);
+/// Randomly seeded per run, so keys can't be steered into collisions.
+static RESPONSE_CACHE_HASHER: Lazy<std::collections::hash_map::RandomState> =
+    Lazy::new(std::collections::hash_map::RandomState::new);
+
+/// Key a request by method, path and query, body, and the `Origin` and
+/// `Authorization` it was made with, so one site's or session's cached
+/// answer is never served to another.
+fn response_cache_key(method: &hyper::Method, uri: &hyper::Uri, headers: &hyper::HeaderMap, body: &str) -> u64 {
+    use std::hash::BuildHasher;
+
+    let header = |name| headers.get(name).map(|v: &hyper::header::HeaderValue| v.as_bytes());
+    RESPONSE_CACHE_HASHER.hash_one((
+        method.as_str(),
+        uri.to_string(),
+        header(hyper::header::ORIGIN),
+        header(hyper::header::AUTHORIZATION),
+        body,
+    ))
+}
+
+/// A fresh cached response for `key`, if there is one.
+fn cached_response(key: u64) -> Option<Response<Body>> {
+    let entry = RESPONSE_CACHE.get(&key)?;
+    if entry.expires_at <= Instant::now() {
+        drop(entry);
+        RESPONSE_CACHE.remove_if(&key, |_, entry| entry.expires_at <= Instant::now());
+        return None;
+    }
+    let mut res = Response::new(Body::from(entry.body.clone()));
+    *res.status_mut() = entry.status;
+    add_renderer_headers(&mut res, &entry.headers);
+    res.headers_mut().insert("X-MND-Cache", "hit".parse().unwrap());
+    add_cors_headers(&mut res);
+    Some(res)
+}
+
+/// Remember a response for `ttl_ms` (capped). When the cache is full,
+/// expired entries are dropped first; if it is still full the response
+/// simply isn't cached.
+fn store_cached_response(key: u64, ttl_ms: u64, status: StatusCode, body: &str, headers: &[(String, String)]) {
+    if RESPONSE_CACHE.len() >= RESPONSE_CACHE_MAX_ENTRIES {
+        let now = Instant::now();
+        RESPONSE_CACHE.retain(|_, entry| entry.expires_at > now);
+        if RESPONSE_CACHE.len() >= RESPONSE_CACHE_MAX_ENTRIES {
+            return;
+        }
+    }
+    let ttl = Duration::from_millis(ttl_ms).min(RESPONSE_CACHE_MAX_TTL);
+    RESPONSE_CACHE.insert(
+        key,
+        CachedResponse { status, body: body.to_string(), headers: headers.to_vec(), expires_at: Instant::now() + ttl },
+    );
+}
+
+/// Drop every cached renderer response.
+#[tauri::command]
+fn clear_response_cache() -> usize {
+    let before = RESPONSE_CACHE.len();
+    RESPONSE_CACHE.clear();
+    before
+}
+
+/// Empties a cache and returns how many entries were dropped.
+type ClearCache = fn() -> usize;
+
+/// Every in-memory cache, by name. New caches register here.
+const CACHE_REGISTRY: &[(&str, ClearCache)] =
+    &[("idempotency", clear_idempotency_cache), ("response", clear_response_cache)];
+
+/// Empty all caches in `CACHE_REGISTRY`, reporting entries cleared per cache.
+#[tauri::command]
+fn clear_caches() -> BTreeMap<String, usize> {
+    CACHE_REGISTRY
+        .iter()
+        .map(|(name, clear)| (name.to_string(), clear()))
+        .collect()
+}
+
+/// A forwarded request waiting for the renderer's answer.
+struct PendingRequest {
+    tx: oneshot::Sender<TsResponse>,
+    since: Instant,
+}
+
+impl PendingRequest {
+    fn new(tx: oneshot::Sender<TsResponse>) -> Self {
+        PendingRequest { tx, since: Instant::now() }
+    }
+}
+
+/// A type alias for our concurrent map of pending responses.
+type PendingMap = DashMap<u64, PendingRequest>;
+
+/// Drop one outstanding request, e.g. when the UI element it belonged to is
+/// dismissed. Its client gets a `cancelled` error. Returns whether it existed.
+#[tauri::command]
+fn cancel_pending_request(pending_requests: State<'_, Arc<PendingMap>>, request_id: u64) -> bool {
+    let existed = pending_requests.remove(&request_id).is_some();
+    if existed {
+        abandon_idempotent_request(request_id);
+    }
+    existed
+}
+
+/// Unix ms of the last `ts-response` (pings included) from the renderer; 0
+/// until the first one.
+static RENDERER_LAST_SEEN_MS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize)]
+struct RendererLastSeen {
+    at_unix_ms: Option<u64>,
+    /// How long ago the renderer last answered. A large value while requests
+    /// are pending means it is hung even though the process is alive.
+    ms_ago: Option<u64>,
+}
+
+#[tauri::command]
+fn get_renderer_last_seen() -> RendererLastSeen {
+    match RENDERER_LAST_SEEN_MS.load(Ordering::Relaxed) {
+        0 => RendererLastSeen { at_unix_ms: None, ms_ago: None },
+        at => RendererLastSeen { at_unix_ms: Some(at), ms_ago: Some(unix_millis().saturating_sub(at)) },
+    }
+}
+
+/// Hand a renderer response to the request waiting on it.
+fn deliver_ts_response(pending_requests: &PendingMap, ts_response: TsResponse) {
+    RENDERER_LAST_SEEN_MS.store(unix_millis(), Ordering::Relaxed);
+    complete_idempotent_request(&ts_response);
+    if let Some((req_id, pending)) = pending_requests.remove(&ts_response.request_id) {
+        if let Err(err) = pending.tx.send(ts_response) {
+            log_error!("Failed to send response via oneshot channel for request {}: {:?}", req_id, err);
+        }
+    } else {
+        log_error!("Received ts-response for unknown request_id: {}", ts_response.request_id);
+    }
+}
+
+/// Entries are only swept this long after the longest allowed renderer
+/// timeout, so the sweeper never races a handler that is still waiting.
+const PENDING_SWEEP_GRACE: Duration = Duration::from_secs(60);
+
+/// Safety net against leaks: periodically drop pending entries that outlived
+/// any possible timeout. Dropping the sender unblocks whoever still waits.
+async fn run_pending_sweeper(pending_requests: Arc<PendingMap>) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(config().pending_sweep_secs.max(1))).await;
+        let max_age = Duration::from_millis(config().max_renderer_timeout_ms) + PENDING_SWEEP_GRACE;
+        let mut swept = 0;
+        pending_requests.retain(|request_id, pending| {
+            let keep = pending.since.elapsed() <= max_age;
+            if !keep {
+                swept += 1;
+                abandon_idempotent_request(*request_id);
+            }
+            keep
+        });
+        if swept > 0 {
+            log_error!("WARN swept {} stale pending request(s)", swept);
+        }
+    }
+}
+
+/// While set, new requests get 503 instead of being forwarded, so the
+/// renderer can quiesce before an account switch or reconfiguration.
+static FORWARDING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Stop forwarding new requests and wait up to `timeout_ms` for pending ones
+/// to finish. Returns how many were still pending. Forwarding stays paused
+/// until `resume_requests`.
+#[tauri::command]
+async fn drain_requests(pending_requests: State<'_, Arc<PendingMap>>, timeout_ms: u64) -> Result<usize, String> {
+    FORWARDING_PAUSED.store(true, Ordering::Relaxed);
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    while !pending_requests.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Ok(pending_requests.len())
+}
+
+/// Resume forwarding after `drain_requests`.
+#[tauri::command]
+fn resume_requests() {
+    FORWARDING_PAUSED.store(false, Ordering::Relaxed);
+}
+
+/// -----
+/// Tauri COMMANDS for focus management
+/// -----
+
+#[cfg(target_os = "macos")]
+static PREV_BUNDLE_ID: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Bundle id of the app that currently has focus, via System Events.
+#[cfg(target_os = "macos")]
+fn frontmost_bundle_id() -> Option<String> {
+    let output = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg("tell application \"System Events\" to get the bundle identifier of the first process whose frontmost is true")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|id| id.trim().to_string())
+}
+
+/// Recover from a stale `PREV_BUNDLE_ID` (e.g. the app it names has quit):
+/// forget it and remember whatever is frontmost now, unless that's us.
+/// Returns the app `relinquish_focus` will now hand focus back to.
+#[tauri::command]
+fn reset_focus_state(app_handle: AppHandle) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    {
+        let own_id = &app_handle.config().identifier;
+        let frontmost = frontmost_bundle_id().filter(|id| !id.is_empty() && id != own_id);
+        let mut prev = PREV_BUNDLE_ID.lock().unwrap();
+        *prev = frontmost.clone();
+        frontmost
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = app_handle;
+        None
+    }
+}
+
+#[tauri::command]
+fn is_focused(window: Window) -> bool {
+    window.is_focused().unwrap_or_default()
+}
+
+/// "Do not disturb": when set, focus requests only flash the taskbar/Dock
+/// instead of raising the window, and notifications are suppressed.
+static DO_NOT_DISTURB: AtomicBool = AtomicBool::new(false);
+
+#[tauri::command]
+fn set_dnd(enabled: bool) {
+    DO_NOT_DISTURB.store(enabled, Ordering::Relaxed);
+}
+
+#[tauri::command]
+fn is_dnd() -> bool {
+    DO_NOT_DISTURB.load(Ordering::Relaxed)
+}
+
+/// Raise the window, or merely request attention while DND is on.
+fn focus_respecting_dnd(window: &Window) {
+    if DO_NOT_DISTURB.load(Ordering::Relaxed) {
+        if let Err(e) = window.request_user_attention(Some(tauri::UserAttentionType::Informational)) {
+            log_error!("request_user_attention error: {}", e);
+        }
+        return;
+    }
+    raise_window(window);
+}
+
+#[tauri::command]
+fn request_focus(window: Window) {
+    focus_respecting_dnd(&window);
+}
+
+/// Platform-specific routine that forcibly brings `window` to the front.
+fn raise_window(window: &Window) {
+    #[cfg(target_os = "macos")]
+    {
+        // Make window visible first - critical for macOS
         // 0. Capture the bundle id of the currently focused app
-        if let Ok(output) = Command::new("osascript")
-            .arg("-e")
-            .arg("tell application \"System Events\" to get the bundle identifier of the first process whose frontmost is true")
-            .output()
+        if let Some(bundle_id) = frontmost_bundle_id() {
+            let mut prev = PREV_BUNDLE_ID.lock().unwrap();
+            *prev = Some(bundle_id);
+        }
+        // 1. "Unminimize" if necessary.
+        if let Err(e) = window.unminimize() {
+            log_error!("(macOS) unminimize error: {}", e);
+        }
+
+        // Ensure the window is shown
+        if let Err(e) = window.show() {
+            log_error!("(macOS) show error: {}", e);
+        }
+
+        // Request user attention (bounces Dock icon)
+        if let Err(e) = window.request_user_attention(Some(tauri::UserAttentionType::Informational))
         {
-            if output.status.success() {
-                if let Ok(bundle_id) = String::from_utf8(output.stdout) {
-                    let bundle_id = bundle_id.trim().to_string();
-                    let mut prev = PREV_BUNDLE_ID.lock().unwrap();
-                    *prev = Some(bundle_id);
+            log_error!("(macOS) request_user_attention error: {}", e);
+        }
+
+        // Focus the window - try multiple times with delays if needed
+        for i in 0..3 {
+            if let Ok(focused) = window.is_focused() {
+                if focused {
+                    break;
+                }
+            }
+
+            if let Err(e) = window.set_focus() {
+                log_error!("(macOS) set_focus attempt {} error: {}", i, e);
+            }
+
+            // Small delay to allow macOS to process the focus request
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Show the window if it's hidden
+        if let Err(e) = window.show() {
+            log_error!("(Windows) show error: {}", e);
+        }
+        // Unminimize the window (important!)
+        if let Err(e) = window.unminimize() {
+            log_error!("(Windows) unminimize error: {}", e);
+        }
+        // Attempt to focus the window directly
+        if let Err(e) = window.set_focus() {
+            log_error!("(Windows) set_focus error: {}", e);
+        }
+        // Temporarily set always-on-top to force focus
+        if let Err(e) = window.set_always_on_top(true) {
+            log_error!("(Windows) set_always_on_top(true) error: {}", e);
+        }
+        // Then go back to the window's own pin state
+        let pinned = is_pinned(window.label());
+        if let Err(e) = window.set_always_on_top(pinned) {
+            log_error!("(Windows) set_always_on_top({}) error: {}", pinned, e);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // First, unminimize the window if it's minimized
+        if let Err(e) = window.unminimize() {
+            log_error!("(Linux) unminimize error: {}", e);
+        }
+
+        // Show the window if it's hidden
+        if let Err(e) = window.show() {
+            log_error!("(Linux) show error: {}", e);
+        }
+
+        // Attempt to focus the window
+        if let Err(e) = window.set_focus() {
+            log_error!("(Linux) set_focus error: {}", e);
+        }
+
+        // On Linux, sometimes we need multiple focus attempts
+        std::thread::sleep(std::time::Duration::from_millis(30));
+        if let Ok(focused) = window.is_focused() {
+            if !focused {
+                if let Err(e) = window.set_focus() {
+                    log_error!("(Linux) set_focus retry error: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Summary of an open window, as reported by `list_windows`.
+#[derive(Serialize)]
+struct WindowInfo {
+    label: String,
+    title: String,
+    focused: bool,
+    visible: bool,
+}
+
+#[tauri::command]
+fn list_windows(app_handle: AppHandle) -> Vec<WindowInfo> {
+    let mut windows: Vec<WindowInfo> = app_handle
+        .webview_windows()
+        .into_iter()
+        .map(|(label, window)| WindowInfo {
+            label,
+            title: window.title().unwrap_or_default(),
+            focused: window.is_focused().unwrap_or_default(),
+            visible: window.is_visible().unwrap_or_default(),
+        })
+        .collect();
+    windows.sort_by(|a, b| a.label.cmp(&b.label));
+    windows
+}
+
+/// Like `request_focus`, but targets the window with the given label rather
+/// than the window that invoked the command.
+#[tauri::command]
+fn focus_window(app_handle: AppHandle, label: String) -> Result<(), String> {
+    let window = app_handle
+        .get_webview_window(&label)
+        .ok_or_else(|| format!("no window with label: {label}"))?;
+    focus_respecting_dnd(&window.as_ref().window());
+    Ok(())
+}
+
+/// Attempt to move the window out of the user's way so they can resume
+/// other tasks. The exact behavior (switch/minimize) differs per platform.
+#[tauri::command]
+fn relinquish_focus(window: Window) {
+    #[cfg(target_os = "linux")]
+    {
+        // Minimize the window instead of hiding
+        if let Err(e) = window.minimize() {
+            log_error!("Linux minimize error: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // Minimize the window instead of hiding
+        if let Err(e) = window.minimize() {
+            log_error!("Windows minimize error: {}", e);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::process::Command;
+        // Try to restore focus to previous app
+        let prev_bundle_id = {
+            let prev = PREV_BUNDLE_ID.lock().unwrap();
+            prev.clone()
+        };
+        if let Some(bundle_id) = prev_bundle_id {
+            if !bundle_id.is_empty() && bundle_id != "com.apple.finder" {
+                let script = format!("tell application id \"{}\" to activate", bundle_id);
+                if let Err(e) = Command::new("osascript").arg("-e").arg(&script).output() {
+                    log_error!("MacOS failed to re-activate previous app: {}", e);
+                }
+            }
+        }
+        _ = window.is_focused();
+    }
+}
+
+/// Gentler alternative to `request_focus`: flash the taskbar entry (or bounce
+/// the Dock icon on macOS) without raising or focusing the window.
+#[tauri::command]
+fn request_attention(window: Window) -> Result<(), String> {
+    window
+        .request_user_attention(Some(tauri::UserAttentionType::Informational))
+        .map_err(|e| e.to_string())
+}
+
+/// Labels of windows pinned above all others, so the transient toggle in the
+/// Windows focus routine can put a pin back instead of clearing it.
+static PINNED_WINDOWS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[cfg(target_os = "windows")]
+fn is_pinned(label: &str) -> bool {
+    PINNED_WINDOWS.lock().unwrap().contains(label)
+}
+
+fn record_pinned(label: &str, pinned: bool) {
+    let mut pinned_windows = PINNED_WINDOWS.lock().unwrap();
+    if pinned {
+        pinned_windows.insert(label.to_string());
+    } else {
+        pinned_windows.remove(label);
+    }
+}
+
+/// Pin (or unpin) the window above all other windows. Unlike the transient
+/// toggle in the Windows focus routine, this stays in effect until changed.
+#[tauri::command]
+fn set_always_on_top(window: Window, enabled: bool) -> Result<(), String> {
+    window
+        .set_always_on_top(enabled)
+        .map_err(|e| e.to_string())?;
+    record_pinned(window.label(), enabled);
+    Ok(())
+}
+
+/// Report whether the window is currently pinned above other windows.
+#[tauri::command]
+fn is_always_on_top(window: Window) -> Result<bool, String> {
+    window.is_always_on_top().map_err(|e| e.to_string())
+}
+
+/// Allow or prevent the user resizing the window, e.g. to lock a compact
+/// approval layout.
+#[tauri::command]
+fn set_resizable(window: Window, resizable: bool) -> Result<(), String> {
+    window.set_resizable(resizable).map_err(|e| e.to_string())
+}
+
+/// Smallest window, in logical pixels, `set_size_constraints` will allow.
+const MIN_WINDOW_WIDTH: f64 = 200.0;
+const MIN_WINDOW_HEIGHT: f64 = 150.0;
+/// Stand-in maximum for an unbounded axis when the monitor is unknown.
+const UNBOUNDED_WINDOW_SIZE: f64 = 100_000.0;
+
+/// The constraints actually applied by `set_size_constraints`.
+#[derive(Serialize)]
+struct SizeConstraints {
+    min_width: f64,
+    min_height: f64,
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+}
+
+/// Set the window's minimum and (optionally) maximum size in logical pixels
+/// and apply them at once, resizing the window if it is now out of bounds.
+/// Minimums are raised to a usable floor and everything is capped at the
+/// current monitor's size. Returns the constraints after clamping.
+#[tauri::command]
+fn set_size_constraints(
+    window: Window,
+    min_width: f64,
+    min_height: f64,
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+) -> Result<SizeConstraints, String> {
+    let sizes = [Some(min_width), Some(min_height), max_width, max_height];
+    if sizes.iter().flatten().any(|v| !v.is_finite() || *v <= 0.0) {
+        return Err("sizes must be positive numbers".into());
+    }
+    if max_width.is_some_and(|max| max < min_width) || max_height.is_some_and(|max| max < min_height) {
+        return Err("maximum size must not be smaller than the minimum".into());
+    }
+
+    let monitor = window
+        .current_monitor()
+        .map_err(|e| e.to_string())?
+        .map(|monitor| monitor.size().to_logical::<f64>(monitor.scale_factor()));
+    let cap = |value: f64, limit: Option<f64>| limit.map_or(value, |limit| value.min(limit));
+    let screen_w = monitor.map(|size| size.width);
+    let screen_h = monitor.map(|size| size.height);
+    let min_width = cap(min_width.max(MIN_WINDOW_WIDTH), screen_w);
+    let min_height = cap(min_height.max(MIN_WINDOW_HEIGHT), screen_h);
+    let max_width = max_width.map(|w| cap(w.max(min_width), screen_w));
+    let max_height = max_height.map(|h| cap(h.max(min_height), screen_h));
+
+    window
+        .set_min_size(Some(tauri::LogicalSize::new(min_width, min_height)))
+        .map_err(|e| e.to_string())?;
+    let max = match (max_width, max_height) {
+        (None, None) => None,
+        // An unbounded axis is limited only by the screen.
+        (w, h) => Some(tauri::LogicalSize::new(
+            w.or(screen_w).unwrap_or(UNBOUNDED_WINDOW_SIZE),
+            h.or(screen_h).unwrap_or(UNBOUNDED_WINDOW_SIZE),
+        )),
+    };
+    window.set_max_size(max).map_err(|e| e.to_string())?;
+
+    // Bring the current size inside the new bounds.
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let current = window.inner_size().map_err(|e| e.to_string())?.to_logical::<f64>(scale);
+    let width = cap(current.width.max(min_width), max_width);
+    let height = cap(current.height.max(min_height), max_height);
+    if (width, height) != (current.width, current.height) {
+        window
+            .set_size(tauri::LogicalSize::new(width, height))
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(SizeConstraints { min_width, min_height, max_width, max_height })
+}
+
+/// Let the window float over other apps' fullscreen spaces, so approval
+/// prompts aren't hidden during presentations or games. Always-on-top alone
+/// isn't enough there; the window also has to join every space as a
+/// fullscreen auxiliary. macOS only.
+#[tauri::command]
+fn set_visible_over_fullscreen(window: Window, enabled: bool) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        use objc2_app_kit::{NSWindow, NSWindowCollectionBehavior};
+
+        window.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+        record_pinned(window.label(), enabled);
+        let target = window.clone();
+        window
+            .run_on_main_thread(move || {
+                let Ok(ptr) = target.ns_window() else {
+                    return;
+                };
+                // SAFETY: `ns_window` is the live NSWindow backing `target`,
+                // and AppKit is only touched from the main thread.
+                let ns_window = unsafe { &*ptr.cast::<NSWindow>() };
+                let mut behavior = ns_window.collectionBehavior();
+                behavior.set(
+                    NSWindowCollectionBehavior::CanJoinAllSpaces | NSWindowCollectionBehavior::FullScreenAuxiliary,
+                    enabled,
+                );
+                ns_window.setCollectionBehavior(behavior);
+            })
+            .map_err(|e| e.to_string())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (window, enabled);
+        Err("showing over fullscreen apps is only supported on macOS".into())
+    }
+}
+
+/// "light" or "dark", or "no-preference" when the platform doesn't report
+/// one (some Linux desktops) or reports something we don't know.
+fn theme_name(theme: Option<tauri::Theme>) -> &'static str {
+    match theme {
+        Some(tauri::Theme::Light) => "light",
+        Some(tauri::Theme::Dark) => "dark",
+        _ => "no-preference",
+    }
+}
+
+/// The OS light/dark preference as seen by this window. Changes arrive as
+/// `theme-changed` events.
+#[tauri::command]
+fn get_system_theme(window: Window) -> &'static str {
+    theme_name(window.theme().ok())
+}
+
+/// Switch between a normal Dock app (`"regular"`) and a background agent
+/// with no Dock icon (`"accessory"`). macOS only.
+#[tauri::command]
+fn set_activation_policy(app_handle: AppHandle, mode: String) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        let policy = match mode.as_str() {
+            "regular" => tauri::ActivationPolicy::Regular,
+            "accessory" => tauri::ActivationPolicy::Accessory,
+            other => return Err(format!("unknown activation policy: {other}")),
+        };
+        app_handle.set_activation_policy(policy).map_err(|e| e.to_string())
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (app_handle, mode);
+        Err("activation policy is only supported on macOS".into())
+    }
+}
+
+#[cfg(target_os = "macos")]
+const SOUND_DIR: &str = "/System/Library/Sounds";
+#[cfg(target_os = "macos")]
+const SOUND_EXTENSION: &str = "aiff";
+#[cfg(target_os = "macos")]
+const DEFAULT_SOUND: &str = "Glass";
+
+#[cfg(all(unix, not(target_os = "macos")))]
+const SOUND_DIR: &str = "/usr/share/sounds/freedesktop/stereo";
+#[cfg(all(unix, not(target_os = "macos")))]
+const SOUND_EXTENSION: &str = "oga";
+#[cfg(all(unix, not(target_os = "macos")))]
+const DEFAULT_SOUND: &str = "message-new-instant";
+
+#[cfg(windows)]
+const WINDOWS_SOUNDS: [&str; 5] = ["Asterisk", "Beep", "Exclamation", "Hand", "Question"];
+#[cfg(windows)]
+const DEFAULT_SOUND: &str = "Asterisk";
+
+/// Names of the system sounds `play_notification_sound` accepts.
+#[tauri::command]
+fn list_notification_sounds() -> Vec<String> {
+    #[cfg(windows)]
+    {
+        WINDOWS_SOUNDS.iter().map(|s| s.to_string()).collect()
+    }
+    #[cfg(unix)]
+    {
+        let mut names: Vec<String> = fs::read_dir(SOUND_DIR)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == SOUND_EXTENSION))
+                    .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+}
+
+/// Play a system sound (the platform default if `name` is `None`) without
+/// waiting for it to finish. Returns `false` when Do Not Disturb is on.
+#[tauri::command]
+fn play_notification_sound(name: Option<String>) -> Result<bool, String> {
+    if DO_NOT_DISTURB.load(Ordering::Relaxed) {
+        return Ok(false);
+    }
+    let name = name.unwrap_or_else(|| DEFAULT_SOUND.to_string());
+    // Only names we listed ourselves, so nothing user-supplied reaches a path or shell.
+    if !list_notification_sounds().contains(&name) {
+        return Err(format!("unknown sound: {name}"));
+    }
+
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = std::process::Command::new("afplay");
+        command.arg(Path::new(SOUND_DIR).join(format!("{name}.{SOUND_EXTENSION}")));
+        command
+    };
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let mut command = {
+        let mut command = std::process::Command::new("paplay");
+        command.arg(Path::new(SOUND_DIR).join(format!("{name}.{SOUND_EXTENSION}")));
+        command
+    };
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &format!("[System.Media.SystemSounds]::{name}.Play()")]);
+        command
+    };
+
+    command.spawn().map(|_| true).map_err(|e| e.to_string())
+}
+
+/// Split a filename into its stem and extension. Multi-part archive
+/// extensions such as `.tar.gz` are kept together so numbering lands before
+/// them (`backup (1).tar.gz`).
+fn split_filename(filename: &str) -> (&str, &str) {
+    let lower = filename.to_ascii_lowercase();
+    for compound in [".tar.gz", ".tar.bz2", ".tar.xz", ".tar.zst"] {
+        if lower.ends_with(compound) && filename.len() > compound.len() {
+            let idx = filename.len() - compound.len();
+            return (&filename[..idx], &filename[idx + 1..]);
+        }
+    }
+    match filename.rfind('.') {
+        // A leading dot marks a hidden file, not an extension.
+        Some(idx) if idx > 0 => (&filename[..idx], &filename[idx + 1..]),
+        _ => (filename, ""),
+    }
+}
+
+/// Split a trailing ` (n)` off `stem`, returning the base and the counter.
+fn split_counter_suffix(stem: &str) -> Option<(&str, &str)> {
+    let inner = stem.strip_suffix(')')?;
+    let idx = inner.rfind(" (")?;
+    let digits = &inner[idx + 2..];
+    (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then(|| (&stem[..idx], digits))
+}
+
+/// Strip a trailing ` (n)` collision counter, so re-downloading
+/// `report (1).pdf` yields `report (2).pdf` rather than `report (1) (1).pdf`.
+/// The suffix only counts as a counter when the unnumbered file exists in
+/// `dir`; otherwise it is part of the name, as in `Movie (2019).mp4`.
+fn strip_counter_suffix<'a>(dir: &Path, stem: &'a str, ext: &str) -> &'a str {
+    match split_counter_suffix(stem) {
+        Some((base, _)) if dir.join(join_filename(base, ext)).exists() => base,
+        _ => stem,
+    }
+}
+
+fn join_filename(stem: &str, ext: &str) -> String {
+    if ext.is_empty() {
+        stem.to_string()
+    } else {
+        format!("{}.{}", stem, ext)
+    }
+}
+
+fn numbered_filename(stem: &str, counter: u32, ext: &str) -> String {
+    join_filename(&format!("{} ({})", stem, counter), ext)
+}
+
+/// Return a path inside `dir` for `filename` that doesn't collide with an
+/// existing file, appending ` (n)` to the stem when needed. Any directory
+/// components in `filename` are discarded so the result always stays in `dir`.
+fn unique_path(dir: &Path, filename: &str) -> Result<PathBuf, String> {
+    let filename = Path::new(filename)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let candidate = dir.join(filename);
+    if !candidate.exists() {
+        return Ok(candidate);
+    }
+
+    let (stem, ext) = split_filename(filename);
+    let stem = strip_counter_suffix(dir, stem, ext);
+    let used = used_counters(dir, stem, ext);
+    (1..=u32::MAX)
+        .filter(|counter| !used.contains(counter))
+        .map(|counter| dir.join(numbered_filename(stem, counter, ext)))
+        .find(|path| !path.exists())
+        .ok_or_else(|| format!("no free name for {} in {}", filename, dir.display()))
+}
+
+/// Counters already taken by ` (n)` siblings of `stem`/`ext` in `dir`, so
+/// numbering fills gaps left by deleted files instead of only counting up.
+fn used_counters(dir: &Path, stem: &str, ext: &str) -> HashSet<u32> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashSet::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|name| {
+            let (entry_stem, entry_ext) = split_filename(&name);
+            match split_counter_suffix(entry_stem) {
+                Some((base, digits)) if entry_ext == ext && base == stem => digits.parse().ok(),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Payload of the `path-fallback` event, telling the UI where a file actually
+/// went when the preferred directory was unavailable.
+#[derive(Serialize, Clone)]
+struct PathFallbackEvent {
+    wanted: String,
+    used: String,
+    reason: String,
+}
+
+fn emit_path_fallback(app_handle: &AppHandle, wanted: &str, used: &Path, reason: String) {
+    log_error!("Falling back from {} to {}: {}", wanted, used.display(), reason);
+    let payload = PathFallbackEvent {
+        wanted: wanted.to_string(),
+        used: used.display().to_string(),
+        reason,
+    };
+    if let Err(err) = app_handle.emit("path-fallback", payload) {
+        log_error!("Failed to emit path-fallback event: {:?}", err);
+    }
+}
+
+/// Resolve the downloads directory, creating it if missing. Minimal Linux
+/// setups without XDG user dirs fall back to `~/Downloads`, then `~`.
+fn resolve_download_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let reason = match app_handle.path().download_dir() {
+        Ok(dir) => match fs::create_dir_all(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) => format!("{} is unusable: {}", dir.display(), e),
+        },
+        Err(e) => format!("download dir unavailable: {}", e),
+    };
+
+    let home = app_handle.path().home_dir().map_err(|e| {
+        log_error!("No home directory to fall back to: {}", e);
+        format!("{}; no home directory: {}", reason, e)
+    })?;
+    let home_downloads = home.join("Downloads");
+    match fs::create_dir_all(&home_downloads) {
+        Ok(()) => {
+            emit_path_fallback(app_handle, "download_dir", &home_downloads, reason);
+            Ok(home_downloads)
+        }
+        Err(e) => {
+            let reason = format!("{}; {} is unusable: {}", reason, home_downloads.display(), e);
+            emit_path_fallback(app_handle, "download_dir", &home, reason);
+            Ok(home)
+        }
+    }
+}
+
+#[command]
+async fn download(app_handle: AppHandle, filename: String, content: Vec<u8>) -> Result<(), String> {
+    let downloads_dir = resolve_download_dir(&app_handle)?;
+
+    let final_path = unique_path(&downloads_dir, &filename)?;
+    let reason = match write_with_progress(&app_handle, &final_path, &content) {
+        Ok(()) => return Ok(()),
+        Err(e) if is_unwritable(&e) => format!("{} is not writable: {}", downloads_dir.display(), e),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    // Managed machines sometimes lock down Downloads; try Documents next.
+    let reason = match app_handle.path().document_dir() {
+        Ok(documents) => match unique_path(&documents, &filename) {
+            Ok(path) => match write_with_progress(&app_handle, &path, &content) {
+                Ok(()) => {
+                    emit_path_fallback(&app_handle, "download_dir", &documents, reason);
+                    return Ok(());
+                }
+                Err(e) => format!("{}; {} is not writable: {}", reason, documents.display(), e),
+            },
+            Err(e) => format!("{}; {}", reason, e),
+        },
+        Err(e) => format!("{}; documents dir unavailable: {}", reason, e),
+    };
+
+    // Last resort: let the user pick somewhere writable.
+    log_error!("No writable download location ({}); asking the user", reason);
+    let Some(picked) = pick_save_path(app_handle.dialog().file().set_file_name(&filename)).await else {
+        return Err(format!("download cancelled: {}", reason));
+    };
+    let path = picked.into_path().map_err(|e| e.to_string())?;
+    write_with_progress(&app_handle, &path, &content).map_err(|e| e.to_string())?;
+    emit_path_fallback(&app_handle, "download_dir", &path, reason);
+    Ok(())
+}
+
+/// Whether a write failed because the location is read-only or off-limits,
+/// rather than for a reason another directory wouldn't fix.
+fn is_unwritable(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::ReadOnlyFilesystem
+    )
+}
+
+#[derive(Serialize)]
+struct ResolvedDownloadPath {
+    path: String,
+    file_name: String,
+    /// True when a file of the requested name exists and a ` (n)` name was picked.
+    renamed: bool,
+}
+
+/// The path `download` would write `filename` to right now, without writing
+/// anything, so the UI can show the de-duplicated name before confirming.
+#[command]
+fn resolve_download_path(app_handle: AppHandle, filename: String) -> Result<ResolvedDownloadPath, String> {
+    let downloads_dir = resolve_download_dir(&app_handle)?;
+    let path = unique_path(&downloads_dir, &filename)?;
+    let file_name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let requested = Path::new(&filename).file_name().and_then(|name| name.to_str()).unwrap_or("file");
+    Ok(ResolvedDownloadPath {
+        path: path.display().to_string(),
+        renamed: file_name != requested,
+        file_name,
+    })
+}
+
+#[derive(Serialize)]
+struct VolumeSpace {
+    total_bytes: u64,
+    free_bytes: u64,
+    /// Free space usable by this user (may be less than `free_bytes`).
+    available_bytes: u64,
+}
+
+/// Space on the volume holding the downloads directory.
+#[tauri::command]
+fn get_download_volume_space(app_handle: AppHandle) -> Result<VolumeSpace, String> {
+    let dir = resolve_download_dir(&app_handle)?;
+    Ok(VolumeSpace {
+        total_bytes: fs2::total_space(&dir).map_err(|e| e.to_string())?,
+        free_bytes: fs2::free_space(&dir).map_err(|e| e.to_string())?,
+        available_bytes: fs2::available_space(&dir).map_err(|e| e.to_string())?,
+    })
+}
+
+// -----
+// Sandboxed file access
+// -----
+
+/// Directories the renderer may read from or write to by path.
+fn sandbox_roots(app_handle: &AppHandle) -> Vec<PathBuf> {
+    let paths = app_handle.path();
+    [
+        paths.download_dir(),
+        paths.document_dir(),
+        paths.app_data_dir(),
+        paths.app_config_dir(),
+    ]
+    .into_iter()
+    .filter_map(Result::ok)
+    .filter_map(|dir| fs::canonicalize(dir).ok())
+    .collect()
+}
+
+/// Resolve `path` and ensure it lies inside one of the sandbox roots. The
+/// file itself may not exist yet, but its parent directory must.
+fn sandboxed_path(app_handle: &AppHandle, path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    let resolved = match fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        Err(_) => {
+            let parent = path.parent().ok_or("invalid path")?;
+            let name = path.file_name().ok_or("invalid path")?;
+            fs::canonicalize(parent).map_err(|e| e.to_string())?.join(name)
+        }
+    };
+    if sandbox_roots(app_handle).iter().any(|root| resolved.starts_with(root)) {
+        Ok(resolved)
+    } else {
+        Err("path is outside the allowed directories".into())
+    }
+}
+
+/// Largest slice `read_file_chunk` returns in one call.
+const MAX_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+#[derive(Serialize)]
+struct FileChunk {
+    data: Vec<u8>,
+    offset: u64,
+    file_size: u64,
+    /// True when this chunk reaches the end of the file.
+    eof: bool,
+}
+
+/// Read up to `len` bytes at `offset` from a sandboxed file, so the renderer
+/// can page through large files without loading them whole.
+#[tauri::command]
+async fn read_file_chunk(app_handle: AppHandle, path: String, offset: u64, len: u64) -> Result<FileChunk, String> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    if len > MAX_CHUNK_BYTES {
+        return Err(format!("len exceeds the {} byte chunk limit", MAX_CHUNK_BYTES));
+    }
+    let path = sandboxed_path(&app_handle, &path)?;
+    let mut file = fs::File::open(&path).map_err(|e| e.to_string())?;
+    let file_size = file.metadata().map_err(|e| e.to_string())?.len();
+    if offset > file_size {
+        return Err(format!("offset {} is past the end of the file ({} bytes)", offset, file_size));
+    }
+
+    let to_read = len.min(file_size - offset);
+    let mut data = vec![0u8; to_read as usize];
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    file.read_exact(&mut data).map_err(|e| e.to_string())?;
+
+    Ok(FileChunk {
+        data,
+        offset,
+        file_size,
+        eof: offset + to_read >= file_size,
+    })
+}
+
+/// Append `contents` to a sandboxed file, creating it if needed, and return
+/// the file's new length. Append mode means log-style writers never need a
+/// read-modify-write.
+#[tauri::command]
+async fn append_to_file(app_handle: AppHandle, path: String, contents: Vec<u8>) -> Result<u64, String> {
+    let path = sandboxed_path(&app_handle, &path)?;
+    append_bytes(&path, &contents).map_err(|e| e.to_string())
+}
+
+fn append_bytes(path: &Path, contents: &[u8]) -> std::io::Result<u64> {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(contents)?;
+    file.flush()?;
+    Ok(file.metadata()?.len())
+}
+
+/// Versions and platform details shared by the support bundle and state
+/// snapshots.
+fn environment_info(app_handle: &AppHandle) -> serde_json::Value {
+    serde_json::json!({
+        "app_version": app_handle.package_info().version.to_string(),
+        "tauri_version": tauri::VERSION,
+        "webview_version": tauri::webview_version().ok(),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "family": std::env::consts::FAMILY,
+        "created_at_unix_ms": unix_millis(),
+    })
+}
+
+/// Show a save dialog without tying up an async runtime worker while it is
+/// open. `None` means the user cancelled.
+async fn pick_save_path(dialog: tauri_plugin_dialog::FileDialogBuilder<tauri::Wry>) -> Option<tauri_plugin_dialog::FilePath> {
+    let (tx, rx) = oneshot::channel();
+    dialog.save_file(move |picked| {
+        let _ = tx.send(picked);
+    });
+    rx.await.ok().flatten()
+}
+
+/// Gather diagnostics into a zip at a location the user picks. Returns the
+/// written path, or `None` if the dialog was cancelled. Nothing secret is
+/// collected: request records carry paths only, `Config` holds no keys, and
+/// URLs in log lines lose their credentials and query strings.
+#[tauri::command]
+async fn create_support_bundle(app_handle: AppHandle) -> Result<Option<String>, String> {
+    let dialog = app_handle
+        .dialog()
+        .file()
+        .set_file_name("metanet-desktop-support.zip")
+        .add_filter("Zip Archive", &["zip"]);
+    let Some(file_path) = pick_save_path(dialog).await else {
+        return Ok(None);
+    };
+    let path = file_path.into_path().map_err(|e| e.to_string())?;
+
+    let environment = environment_info(&app_handle);
+    let recent_requests: Vec<RequestRecord> = RECENT_REQUESTS.lock().unwrap().iter().cloned().collect();
+    let entries = [
+        ("environment.json", environment),
+        ("config.json", serde_json::to_value(config()).map_err(|e| e.to_string())?),
+        ("request-metrics.json", serde_json::to_value(get_request_metrics()).map_err(|e| e.to_string())?),
+        ("recent-requests.json", serde_json::to_value(recent_requests).map_err(|e| e.to_string())?),
+    ];
+
+    let file = fs::File::create(&path).map_err(|e| e.to_string())?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, value) in entries {
+        let contents = serde_json::to_vec_pretty(&value).map_err(|e| e.to_string())?;
+        zip.start_file(name, options).map_err(|e| e.to_string())?;
+        zip.write_all(&contents).map_err(|e| e.to_string())?;
+    }
+    let log: String = LOG_LINES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(at_unix_ms, line)| format!("{} {}\n", at_unix_ms, redact_urls(line)))
+        .collect();
+    zip.start_file("log.txt", options).map_err(|e| e.to_string())?;
+    zip.write_all(log.as_bytes()).map_err(|e| e.to_string())?;
+    zip.finish().map_err(|e| e.to_string())?;
+
+    Ok(Some(path.display().to_string()))
+}
+
+// -----
+// Idle auto-lock
+// -----
+
+/// Last time a request was forwarded or the main window gained focus.
+static LAST_ACTIVITY: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+/// Whether `auto-lock` has already fired for the current idle period.
+static AUTO_LOCKED: AtomicBool = AtomicBool::new(false);
+
+fn touch_activity() {
+    *LAST_ACTIVITY.lock().unwrap() = Instant::now();
+    AUTO_LOCKED.store(false, Ordering::Relaxed);
+}
+
+/// Periodically check for inactivity and emit `auto-lock` once per idle period.
+async fn run_auto_lock_timer(main_window: WebviewWindow) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let timeout_secs = config().auto_lock_secs;
+        if timeout_secs == 0 || AUTO_LOCKED.load(Ordering::Relaxed) {
+            continue;
+        }
+        let idle = LAST_ACTIVITY.lock().unwrap().elapsed();
+        if idle >= Duration::from_secs(timeout_secs) {
+            AUTO_LOCKED.store(true, Ordering::Relaxed);
+            if let Err(err) = main_window.emit("auto-lock", idle.as_secs()) {
+                log_error!("Failed to emit auto-lock event: {:?}", err);
+            }
+        }
+    }
+}
+
+/// Set the inactivity timeout in seconds. 0 disables auto-lock.
+#[tauri::command]
+fn set_auto_lock_timeout(secs: u64) {
+    CONFIG.write().unwrap().auto_lock_secs = secs;
+    touch_activity();
+}
+
+#[tauri::command]
+fn get_auto_lock_timeout() -> u64 {
+    config().auto_lock_secs
+}
+
+// -----
+// Diagnostics
+// -----
+
+/// When the app started, captured once in `setup` and kept as managed state.
+struct StartTime {
+    instant: Instant,
+    unix_ms: u64,
+}
+
+#[derive(Serialize)]
+struct Uptime {
+    uptime_secs: u64,
+    started_at_unix_ms: u64,
+}
+
+#[tauri::command]
+fn get_uptime(start_time: State<'_, StartTime>) -> Uptime {
+    Uptime {
+        uptime_secs: start_time.instant.elapsed().as_secs(),
+        started_at_unix_ms: start_time.unix_ms,
+    }
+}
+
+/// Whether the webview inspector may stay open. Only meaningful in debug
+/// builds; release builds don't enable Tauri's `devtools` feature.
+static DEVTOOLS_ALLOWED: AtomicBool = AtomicBool::new(true);
+
+/// Best-effort devtools state: "open" or "closed" where Tauri can tell,
+/// "unknown" where it can't (Windows), and "disabled" in builds without devtools.
+#[tauri::command]
+fn is_devtools_open(window: WebviewWindow) -> &'static str {
+    #[cfg(all(debug_assertions, not(windows)))]
+    {
+        if window.is_devtools_open() {
+            "open"
+        } else {
+            "closed"
+        }
+    }
+    #[cfg(all(debug_assertions, windows))]
+    {
+        let _ = window;
+        "unknown"
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = window;
+        "disabled"
+    }
+}
+
+/// Allow or forbid the inspector. While forbidden it is closed now and
+/// whenever it is reopened.
+#[tauri::command]
+fn set_devtools_allowed(window: WebviewWindow, allowed: bool) -> Result<(), String> {
+    #[cfg(debug_assertions)]
+    {
+        DEVTOOLS_ALLOWED.store(allowed, Ordering::Relaxed);
+        if !allowed {
+            window.close_devtools();
+        }
+        Ok(())
+    }
+    #[cfg(not(debug_assertions))]
+    {
+        let _ = window;
+        if allowed {
+            return Err("devtools are not available in this build".into());
+        }
+        DEVTOOLS_ALLOWED.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Keep devtools closed while `DEVTOOLS_ALLOWED` is off. Closing is a no-op
+/// when they aren't open, which also covers Windows where we can't ask.
+#[cfg(debug_assertions)]
+async fn run_devtools_guard(window: WebviewWindow) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if !DEVTOOLS_ALLOWED.load(Ordering::Relaxed) {
+            window.close_devtools();
+        }
+    }
+}
+
+/// Kept between calls so CPU usage is measured over the interval since the
+/// previous query.
+static SYSTEM: Lazy<Mutex<System>> = Lazy::new(|| Mutex::new(System::new()));
+
+#[derive(Serialize)]
+struct ResourceUsage {
+    rss_bytes: u64,
+    /// Percentage of a single core since the previous call (0 on the first).
+    cpu_percent: f32,
+    pending_requests: usize,
+}
+
+#[tauri::command]
+fn get_resource_usage(pending_requests: State<'_, Arc<PendingMap>>) -> Result<ResourceUsage, String> {
+    let pid = sysinfo::get_current_pid().map_err(|e| e.to_string())?;
+    let mut system = SYSTEM.lock().unwrap();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+    let process = system.process(pid).ok_or("current process not found")?;
+    Ok(ResourceUsage {
+        rss_bytes: process.memory(),
+        cpu_percent: process.cpu_usage(),
+        pending_requests: pending_requests.len(),
+    })
+}
+
+#[derive(Serialize)]
+struct PendingAge {
+    request_id: u64,
+    age_ms: u64,
+}
+
+/// Everything `capture_state_snapshot` gathers, in one document.
+#[derive(Serialize)]
+struct StateSnapshot {
+    environment: serde_json::Value,
+    uptime: Uptime,
+    request_metrics: RequestMetricsSnapshot,
+    pending_requests: Vec<PendingAge>,
+    recent_errors: Vec<RecentError>,
+    effective_config: EffectiveConfig,
+    bind_diagnostics: Option<BindDiagnostics>,
+    subscribers: Vec<Subscriber>,
+    /// Where the snapshot was written, when asked to write it.
+    written_to: Option<String>,
+}
+
+/// Strip credentials and query strings from any URLs in `text`; tokens
+/// tend to travel there.
+fn redact_urls(text: &str) -> String {
+    text.split(' ')
+        .map(|word| match Url::parse(word) {
+            Ok(mut url) if url.has_host() => {
+                let _ = url.set_username("");
+                let _ = url.set_password(None);
+                url.set_query(None);
+                url.set_fragment(None);
+                url.to_string()
+            }
+            _ => word.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Capture the server's state in one go for a crash report: stats, pending
+/// requests with their ages, recent errors, effective config and
+/// environment. With `write`, it is also saved under the app log directory.
+#[tauri::command]
+fn capture_state_snapshot(
+    app_handle: AppHandle,
+    start_time: State<'_, StartTime>,
+    pending_requests: State<'_, Arc<PendingMap>>,
+    write: Option<bool>,
+) -> Result<StateSnapshot, String> {
+    let mut pending: Vec<PendingAge> = pending_requests
+        .iter()
+        .map(|entry| PendingAge { request_id: *entry.key(), age_ms: entry.since.elapsed().as_millis() as u64 })
+        .collect();
+    pending.sort_by_key(|p| p.request_id);
+    let recent_errors = get_recent_errors()
+        .into_iter()
+        .map(|error| RecentError { message: redact_urls(&error.message), ..error })
+        .collect();
+
+    let mut snapshot = StateSnapshot {
+        environment: environment_info(&app_handle),
+        uptime: get_uptime(start_time),
+        request_metrics: get_request_metrics(),
+        pending_requests: pending,
+        recent_errors,
+        effective_config: get_effective_config()?,
+        bind_diagnostics: BIND_DIAGNOSTICS.get().cloned(),
+        subscribers: list_subscribers(),
+        written_to: None,
+    };
+
+    if write.unwrap_or(false) {
+        let dir = app_handle.path().app_log_dir().map_err(|e| e.to_string())?;
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = dir.join(format!("state-snapshot-{}.json", unix_millis()));
+        let json = serde_json::to_vec_pretty(&snapshot).map_err(|e| e.to_string())?;
+        fs::write(&path, json).map_err(|e| e.to_string())?;
+        snapshot.written_to = Some(path.display().to_string());
+    }
+    Ok(snapshot)
+}
+
+/// Upper bounds for `benchmark_server`, so a typo can't hammer the app.
+const BENCHMARK_MAX_REQUESTS: u32 = 100_000;
+const BENCHMARK_MAX_CONCURRENCY: u32 = 256;
+
+#[derive(Serialize)]
+struct BenchmarkReport {
+    target: String,
+    requests: u32,
+    concurrency: u32,
+    errors: u32,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    requests_per_sec: f64,
+}
+
+/// Latency at percentile `p` (0-100) of already-sorted samples.
+fn percentile_ms(sorted: &[Duration], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank].as_secs_f64() * 1000.0
+}
+
+/// The built-in path the benchmark hits: the echo endpoint if enabled, else
+/// a swallowed path. Either way the renderer isn't involved.
+fn benchmark_path() -> Result<String, String> {
+    let config = config();
+    if config.debug_endpoints {
+        return Ok(DEBUG_ECHO_PATH.to_string());
+    }
+    config
+        .swallow_paths
+        .into_iter()
+        .next()
+        .ok_or_else(|| "no built-in endpoint is configured to benchmark against".to_string())
+}
+
+/// Fire `requests` GETs at a built-in endpoint of our own server from
+/// `concurrency` workers and report latency percentiles and throughput.
+#[tauri::command]
+async fn benchmark_server(requests: u32, concurrency: u32) -> Result<BenchmarkReport, String> {
+    let requests = requests.clamp(1, BENCHMARK_MAX_REQUESTS);
+    let concurrency = concurrency.clamp(1, BENCHMARK_MAX_CONCURRENCY).min(requests);
+    let addr = BOUND_ADDR.get().ok_or("server is not listening yet")?;
+    let target = format!("http://{}{}", addr, benchmark_path()?);
+    let client = Client::builder()
+        .no_proxy()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let remaining = Arc::new(AtomicU64::new(requests as u64));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let target = target.clone();
+            let remaining = remaining.clone();
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                let mut errors = 0u32;
+                while remaining
+                    .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    let sent = Instant::now();
+                    match client.get(&target).send().await {
+                        Ok(resp) if resp.status().is_success() => samples.push(sent.elapsed()),
+                        _ => errors += 1,
+                    }
+                }
+                (samples, errors)
+            })
+        })
+        .collect();
+
+    let mut samples = Vec::with_capacity(requests as usize);
+    let mut errors = 0;
+    for worker in workers {
+        let (worker_samples, worker_errors) = worker.await.map_err(|e| e.to_string())?;
+        samples.extend(worker_samples);
+        errors += worker_errors;
+    }
+    let elapsed = started.elapsed();
+    samples.sort();
+
+    Ok(BenchmarkReport {
+        target,
+        requests,
+        concurrency,
+        errors,
+        p50_ms: percentile_ms(&samples, 50.0),
+        p95_ms: percentile_ms(&samples, 95.0),
+        p99_ms: percentile_ms(&samples, 99.0),
+        requests_per_sec: samples.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+    })
+}
+
+/// How long `ping_renderer` waits for the pong before giving up.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Round-trip a `renderer-ping` through the same pending-map path used for
+/// HTTP requests and return the latency in milliseconds.
+#[tauri::command]
+async fn ping_renderer(
+    app_handle: AppHandle,
+    pending_requests: State<'_, Arc<PendingMap>>,
+    request_counter: State<'_, Arc<AtomicU64>>,
+) -> Result<u64, String> {
+    let main_window = app_handle
+        .get_webview_window(MAIN_WINDOW_NAME)
+        .ok_or("main window not found")?;
+    let request_id = request_counter.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = oneshot::channel();
+    pending_requests.insert(request_id, PendingRequest::new(tx));
+
+    let started = Instant::now();
+    if let Err(err) = main_window.emit("renderer-ping", request_id) {
+        pending_requests.remove(&request_id);
+        return Err(err.to_string());
+    }
+    match tokio::time::timeout(PING_TIMEOUT, rx).await {
+        Ok(Ok(_)) => Ok(started.elapsed().as_millis() as u64),
+        Ok(Err(_)) => Err("renderer dropped the ping".into()),
+        Err(_) => {
+            pending_requests.remove(&request_id);
+            Err(format!("renderer did not respond within {}ms", PING_TIMEOUT.as_millis()))
+        }
+    }
+}
+
+/// One check in the connectivity wizard, emitted as `wizard-step`.
+#[derive(Serialize, Clone)]
+struct WizardStep {
+    index: usize,
+    total: usize,
+    name: String,
+    passed: bool,
+    details: String,
+}
+
+#[derive(Serialize)]
+struct WizardSummary {
+    passed: usize,
+    failed: usize,
+    steps: Vec<WizardStep>,
+}
+
+/// Run the onboarding connectivity checks in order (server bound, renderer
+/// answering pings, each of `hosts` reachable, proxy environment), emitting
+/// a `wizard-step` event as each finishes. Every check has its own short
+/// timeout, so the whole run stays quick.
+#[tauri::command]
+async fn run_connectivity_wizard(
+    app_handle: AppHandle,
+    pending_requests: State<'_, Arc<PendingMap>>,
+    request_counter: State<'_, Arc<AtomicU64>>,
+    hosts: Vec<String>,
+) -> Result<WizardSummary, String> {
+    let total = hosts.len() + 3;
+    let mut steps = Vec::with_capacity(total);
+    let mut finish = |name: String, result: Result<String, String>| {
+        let (passed, details) = match result {
+            Ok(details) => (true, details),
+            Err(details) => (false, details),
+        };
+        let step = WizardStep { index: steps.len(), total, name, passed, details };
+        if let Err(err) = app_handle.emit("wizard-step", step.clone()) {
+            log_error!("Failed to emit wizard-step event: {:?}", err);
+        }
+        steps.push(step);
+    };
+
+    let bound = match BOUND_ADDR.get() {
+        Some(addr) => Ok(format!("listening on {}", addr)),
+        None => match BIND_DIAGNOSTICS.get() {
+            Some(diag) if diag.preferred_port_taken => {
+                Err(format!("port {} is held by another instance", diag.preferred_port))
+            }
+            _ => Err("server is not listening".to_string()),
+        },
+    };
+    finish("server-bound".to_string(), bound);
+
+    let ping = ping_renderer(app_handle.clone(), pending_requests, request_counter).await;
+    finish("renderer-responsive".to_string(), ping.map(|ms| format!("answered in {}ms", ms)));
+
+    let client = proxy_client_builder()
+        .timeout(Duration::from_secs(3))
+        .build()
+        .map_err(|e| e.to_string())?;
+    for host in hosts {
+        let probe = probe_host(&client, host.trim()).await;
+        let result = match probe.latency_ms {
+            Some(ms) if probe.reachable => Ok(format!("reachable in {}ms", ms)),
+            _ => Err(probe.error.unwrap_or_else(|| "unreachable".to_string())),
+        };
+        finish(format!("host-reachable:{}", probe.host), result);
+    }
+
+    let proxy_vars: Vec<String> = PROXY_ENV_VARS
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| format!("{}={}", name, redact_proxy_url(&value))))
+        .collect();
+    let proxy = if proxy_vars.is_empty() {
+        "no proxy variables set".to_string()
+    } else {
+        proxy_vars.join(", ")
+    };
+    finish("proxy-env".to_string(), Ok(proxy));
+
+    let passed = steps.iter().filter(|step| step.passed).count();
+    Ok(WizardSummary { passed, failed: steps.len() - passed, steps })
+}
+
+// -----
+// Approval window
+// -----
+
+static APPROVAL_WINDOW_NAME: &str = "approval";
+
+/// The approval payload currently awaiting a decision. Kept around so a freshly
+/// created approval window can fetch it once its frontend has loaded.
+static PENDING_APPROVAL: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Show the approval window (creating it if needed) and hand it the payload.
+fn show_approval_window(app_handle: &AppHandle, payload: String) {
+    *PENDING_APPROVAL.lock().unwrap() = Some(payload.clone());
+
+    // Reuse the existing window if one is already open.
+    if let Some(window) = app_handle.get_webview_window(APPROVAL_WINDOW_NAME) {
+        if let Err(err) = app_handle.emit_to(APPROVAL_WINDOW_NAME, "approval-request", payload) {
+            log_error!("Failed to emit approval-request event: {:?}", err);
+        }
+        raise_window(&window.as_ref().window());
+        return;
+    }
+
+    let window = match WebviewWindowBuilder::new(
+        app_handle,
+        APPROVAL_WINDOW_NAME,
+        WebviewUrl::App("index.html#/approval".into()),
+    )
+    .title("Approval Required")
+    .inner_size(480.0, 640.0)
+    .resizable(false)
+    .always_on_top(true)
+    .center()
+    .build()
+    {
+        Ok(window) => window,
+        Err(e) => {
+            log_error!("Failed to create approval window: {}", e);
+            return;
+        }
+    };
+    record_pinned(APPROVAL_WINDOW_NAME, true);
+
+    // Forward the decision to the main window as `approval-decided`, then close
+    // the approval window. The forwarded event uses a different name and a
+    // targeted emit so it never re-enters this listener.
+    let listener = {
+        let app_handle = app_handle.clone();
+        window.listen("approval-decision", move |event| {
+            PENDING_APPROVAL.lock().unwrap().take();
+            if let Err(err) = app_handle.emit_to(MAIN_WINDOW_NAME, "approval-decided", event.payload()) {
+                log_error!("Failed to forward approval decision: {:?}", err);
+            }
+            if let Some(approval_window) = app_handle.get_webview_window(APPROVAL_WINDOW_NAME) {
+                if let Err(e) = approval_window.close() {
+                    log_error!("Failed to close approval window: {}", e);
+                }
+            }
+        })
+    };
+
+    // The listener outlives the window, so drop it with the window; otherwise
+    // every later approval window would forward each decision once more.
+    let app_handle = app_handle.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            app_handle.unlisten(listener);
+        }
+    });
+}
+
+/// Returns the approval payload awaiting a decision, if any.
+#[tauri::command]
+fn get_pending_approval() -> Option<String> {
+    PENDING_APPROVAL.lock().unwrap().clone()
+}
+
+// -----
+// Read-only file viewer windows
+// -----
+
+static PREVIEW_SCHEME: &str = "preview";
+/// Files larger than this are refused by the preview protocol.
+const PREVIEW_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Files currently open in a viewer window, keyed by viewer id. Only files
+/// registered here are reachable through the preview protocol.
+static PREVIEW_FILES: Lazy<DashMap<u64, PathBuf>> = Lazy::new(DashMap::new);
+static PREVIEW_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Serve a registered preview file as plain text so nothing in it executes.
+fn preview_protocol_response(request: &tauri::http::Request<Vec<u8>>) -> tauri::http::Response<Vec<u8>> {
+    let respond = |status: u16, body: Vec<u8>| {
+        tauri::http::Response::builder()
+            .status(status)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .header("Content-Security-Policy", "default-src 'none'")
+            .body(body)
+            .unwrap()
+    };
+
+    let id = request.uri().path().trim_start_matches('/').parse::<u64>().ok();
+    let Some(path) = id.and_then(|id| PREVIEW_FILES.get(&id).map(|p| p.clone())) else {
+        return respond(404, b"Not Found".to_vec());
+    };
+    match fs::metadata(&path) {
+        Ok(meta) if meta.len() > PREVIEW_MAX_BYTES => {
+            respond(413, b"File too large to preview".to_vec())
+        }
+        Ok(_) => match fs::read(&path) {
+            Ok(contents) => respond(200, contents),
+            Err(e) => respond(500, e.to_string().into_bytes()),
+        },
+        Err(e) => respond(500, e.to_string().into_bytes()),
+    }
+}
+
+/// Open a separate read-only window showing the contents of a local file,
+/// e.g. to inspect an export before sharing it. Only files inside the sandbox
+/// roots can be previewed. Returns the window label.
+#[tauri::command]
+async fn open_file_viewer(app_handle: AppHandle, path: String) -> Result<String, String> {
+    let path = sandboxed_path(&app_handle, &path)?;
+    if !path.is_file() {
+        return Err("path is not a file".into());
+    }
+    let title = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let id = PREVIEW_COUNTER.fetch_add(1, Ordering::Relaxed);
+    PREVIEW_FILES.insert(id, path);
+
+    // Custom schemes are exposed as http://<scheme>.localhost on Windows and Android.
+    let url = if cfg!(any(windows, target_os = "android")) {
+        format!("http://{}.localhost/{}", PREVIEW_SCHEME, id)
+    } else {
+        format!("{}://localhost/{}", PREVIEW_SCHEME, id)
+    };
+    let label = format!("viewer-{}", id);
+    let window = WebviewWindowBuilder::new(
+        &app_handle,
+        &label,
+        WebviewUrl::CustomProtocol(url.parse().map_err(|e: url::ParseError| e.to_string())?),
+    )
+    .title(format!("Preview - {}", title))
+    .inner_size(800.0, 600.0)
+    .build()
+    .map_err(|e| {
+        PREVIEW_FILES.remove(&id);
+        e.to_string()
+    })?;
+
+    // Forget the file once its viewer is gone.
+    window.on_window_event(move |event| {
+        if let WindowEvent::Destroyed = event {
+            PREVIEW_FILES.remove(&id);
+        }
+    });
+
+    Ok(label)
+}
+
+// -----
+// Window geometry persistence
+// -----
+
+static WINDOW_STATE_FILE: &str = "window-state.json";
+
+/// Last known geometry of the main window, persisted across restarts.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct WindowGeometry {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    maximized: bool,
+}
+
+/// The most recent non-maximized geometry, so un-maximizing after a restart
+/// returns the window to where the user left it.
+static LAST_GEOMETRY: Lazy<Mutex<Option<WindowGeometry>>> = Lazy::new(|| Mutex::new(None));
+
+fn window_state_path(app_handle: &AppHandle) -> Option<PathBuf> {
+    app_handle
+        .path()
+        .app_config_dir()
+        .ok()
+        .map(|dir| dir.join(WINDOW_STATE_FILE))
+}
+
+fn load_window_geometry(app_handle: &AppHandle) -> Option<WindowGeometry> {
+    let path = window_state_path(app_handle)?;
+    let contents = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Snapshot the window's current geometry and write it to the config dir.
+fn save_window_geometry(window: &WebviewWindow) {
+    // Minimized windows report bogus positions on some platforms.
+    if window.is_minimized().unwrap_or(false) {
+        return;
+    }
+    let maximized = window.is_maximized().unwrap_or(false);
+
+    let geometry = {
+        let mut last = LAST_GEOMETRY.lock().unwrap();
+        if !maximized {
+            if let (Ok(pos), Ok(size)) = (window.outer_position(), window.inner_size()) {
+                *last = Some(WindowGeometry {
+                    x: pos.x,
+                    y: pos.y,
+                    width: size.width,
+                    height: size.height,
+                    maximized: false,
+                });
+            }
+        }
+        match *last {
+            Some(geometry) => WindowGeometry { maximized, ..geometry },
+            None => return,
+        }
+    };
+
+    let Some(path) = window_state_path(window.app_handle()) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            log_error!("Failed to create config dir for window state: {}", e);
+            return;
+        }
+    }
+    match serde_json::to_string(&geometry) {
+        Ok(json) => {
+            if let Err(e) = fs::write(&path, json) {
+                log_error!("Failed to save window state: {}", e);
+            }
+        }
+        Err(e) => log_error!("Failed to serialize window state: {:?}", e),
+    }
+}
+
+/// The primary monitor, or the first connected one on platforms that don't
+/// name a primary. Used whenever a saved or requested monitor isn't connected.
+fn primary_or_first_monitor(window: &Window, monitors: &[tauri::Monitor]) -> Option<tauri::Monitor> {
+    window
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .or_else(|| monitors.first().cloned())
+}
+
+/// Restore a saved geometry on the monitor it was saved on, i.e. the one
+/// holding its centre, clamped to that monitor's bounds. If that monitor is
+/// no longer connected the window is centred on the primary monitor instead,
+/// so it is never stranded off-screen.
+fn restore_window_geometry(window: &WebviewWindow, geometry: WindowGeometry) {
+    let monitors = window.available_monitors().unwrap_or_default();
+    let (cx, cy) = (
+        geometry.x + (geometry.width / 2) as i32,
+        geometry.y + (geometry.height / 2) as i32,
+    );
+    let contains_centre = |m: &&tauri::Monitor| {
+        let (mx, my) = (m.position().x, m.position().y);
+        let (mw, mh) = (m.size().width as i32, m.size().height as i32);
+        (mx..mx + mw).contains(&cx) && (my..my + mh).contains(&cy)
+    };
+    let (monitor, saved_monitor_connected) = match monitors.iter().find(contains_centre) {
+        Some(monitor) => (Some(monitor.clone()), true),
+        None => (primary_or_first_monitor(&window.as_ref().window(), &monitors), false),
+    };
+
+    let mut restored = geometry;
+    if let Some(monitor) = monitor {
+        let (mx, my) = (monitor.position().x, monitor.position().y);
+        let (mw, mh) = (monitor.size().width, monitor.size().height);
+        restored.width = restored.width.min(mw);
+        restored.height = restored.height.min(mh);
+        if saved_monitor_connected {
+            restored.x = restored.x.clamp(mx, mx + (mw - restored.width) as i32);
+            restored.y = restored.y.clamp(my, my + (mh - restored.height) as i32);
+        } else {
+            log_error!("Saved window position is off every connected monitor; using the primary monitor");
+            restored.x = mx + ((mw - restored.width) / 2) as i32;
+            restored.y = my + ((mh - restored.height) / 2) as i32;
+        }
+    }
+
+    if let Err(e) = window.set_size(PhysicalSize::new(restored.width, restored.height)) {
+        log_error!("Failed to restore window size: {}", e);
+    }
+    if let Err(e) = window.set_position(PhysicalPosition::new(restored.x, restored.y)) {
+        log_error!("Failed to restore window position: {}", e);
+    }
+    *LAST_GEOMETRY.lock().unwrap() = Some(WindowGeometry { maximized: false, ..restored });
+    if restored.maximized {
+        if let Err(e) = window.maximize() {
+            log_error!("Failed to restore maximized state: {}", e);
+        }
+    }
+}
+
+// -----
+// Install identity
+// -----
+
+static INSTALL_ID_FILE: &str = "install-id";
+
+static INSTALL_ID: OnceCell<String> = OnceCell::new();
+
+/// Read the persisted install id, creating it on first run. The new id is
+/// written to a temp file and hard-linked into place, so two racing
+/// launches agree on whichever landed first and nobody sees a partial file.
+fn load_or_create_install_id(dir: &Path) -> Result<String, String> {
+    let path = dir.join(INSTALL_ID_FILE);
+    if let Ok(existing) = fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let tmp = dir.join(format!("{}.{}.tmp", INSTALL_ID_FILE, std::process::id()));
+    fs::write(&tmp, &id).map_err(|e| e.to_string())?;
+    let linked = fs::hard_link(&tmp, &path);
+    let _ = fs::remove_file(&tmp);
+    match linked {
+        Ok(()) => Ok(id),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            fs::read_to_string(&path).map(|s| s.trim().to_string()).map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A random identifier created once per install and reused forever after.
+/// It contains no hardware or user information.
+#[tauri::command]
+fn get_install_fingerprint(app_handle: AppHandle) -> Result<String, String> {
+    INSTALL_ID
+        .get_or_try_init(|| {
+            let dir = app_handle.path().app_config_dir().map_err(|e| e.to_string())?;
+            load_or_create_install_id(&dir)
+        })
+        .cloned()
+}
+
+// -----
+// Settings store
+// -----
+
+static SETTINGS_FILE: &str = "settings.json";
+
+/// Values by key, grouped by namespace so unrelated features can't collide.
+type SettingsMap = BTreeMap<String, BTreeMap<String, String>>;
+
+/// In-memory copy of the settings file, loaded on first use.
+static SETTINGS: Lazy<Mutex<Option<SettingsMap>>> = Lazy::new(|| Mutex::new(None));
+
+fn settings_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app_handle.path().app_config_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(SETTINGS_FILE))
+}
+
+/// Write via a temp file and rename, so a crash never leaves a torn file.
+fn write_json_atomic(path: &Path, value: &impl Serialize) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_vec_pretty(value).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    fs::rename(&tmp, path).map_err(|e| e.to_string())
+}
+
+/// Run `f` on the cached settings, loading them from disk first if needed.
+fn with_settings<T>(app_handle: &AppHandle, f: impl FnOnce(&mut SettingsMap, &Path) -> Result<T, String>) -> Result<T, String> {
+    let path = settings_path(app_handle)?;
+    let mut cached = SETTINGS.lock().unwrap();
+    let settings = cached.get_or_insert_with(|| {
+        fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    });
+    f(settings, &path)
+}
+
+/// Read a stored value. Values are opaque strings; callers JSON-encode them.
+#[tauri::command]
+fn kv_get(app_handle: AppHandle, namespace: String, key: String) -> Result<Option<String>, String> {
+    with_settings(&app_handle, |settings, _| {
+        Ok(settings.get(&namespace).and_then(|values| values.get(&key)).cloned())
+    })
+}
+
+/// Store a value, or delete it when `value` is `None`, and persist at once.
+#[tauri::command]
+fn kv_set(app_handle: AppHandle, namespace: String, key: String, value: Option<String>) -> Result<(), String> {
+    with_settings(&app_handle, |settings, path| {
+        match value {
+            Some(value) => {
+                settings.entry(namespace).or_default().insert(key, value);
+            }
+            None => {
+                if let Some(values) = settings.get_mut(&namespace) {
+                    values.remove(&key);
+                    if values.is_empty() {
+                        settings.remove(&namespace);
+                    }
                 }
             }
         }
-        // 1. "Unminimize" if necessary.
-        if let Err(e) = window.unminimize() {
-            eprintln!("(macOS) unminimize error: {}", e);
+        write_json_atomic(path, settings)
+    })
+}
+
+// -----
+// Format validation
+// -----
+
+/// Result of `validate_address`.
+#[derive(Serialize)]
+struct AddressValidation {
+    valid: bool,
+    /// "mainnet" or "testnet" for valid addresses.
+    network: Option<&'static str>,
+    error: Option<String>,
+}
+
+/// Result of `validate_outpoint`.
+#[derive(Serialize)]
+struct OutpointValidation {
+    valid: bool,
+    txid: Option<String>,
+    vout: Option<u32>,
+    error: Option<String>,
+}
+
+/// Check a base58check P2PKH address and return its network.
+fn parse_address(addr: &str) -> Result<&'static str, String> {
+    let payload = bs58::decode(addr.trim())
+        .with_check(None)
+        .into_vec()
+        .map_err(|e| format!("invalid base58check: {e}"))?;
+    // One version byte followed by a 20-byte public key hash.
+    if payload.len() != 21 {
+        return Err(format!("expected 21-byte payload, got {}", payload.len()));
+    }
+    match payload[0] {
+        0x00 => Ok("mainnet"),
+        0x6f => Ok("testnet"),
+        version => Err(format!("unsupported address version byte: {version:#04x}")),
+    }
+}
+
+/// Split a BRC-100 outpoint string (`<txid>.<vout>`) into its parts.
+fn parse_outpoint(outpoint: &str) -> Result<(String, u32), String> {
+    let (txid, vout) = outpoint
+        .trim()
+        .split_once('.')
+        .ok_or("expected <txid>.<vout>")?;
+    if txid.len() != 64 || !txid.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("txid must be 64 hex characters".into());
+    }
+    let vout = vout
+        .parse::<u32>()
+        .map_err(|_| format!("invalid output index: {vout}"))?;
+    Ok((txid.to_ascii_lowercase(), vout))
+}
+
+/// Format-only check of a BSV address; no network lookups are performed.
+#[tauri::command]
+fn validate_address(addr: String) -> AddressValidation {
+    match parse_address(&addr) {
+        Ok(network) => AddressValidation { valid: true, network: Some(network), error: None },
+        Err(e) => AddressValidation { valid: false, network: None, error: Some(e) },
+    }
+}
+
+/// Format-only check of an outpoint string such as `<txid>.0`.
+#[tauri::command]
+fn validate_outpoint(txid_vout: String) -> OutpointValidation {
+    match parse_outpoint(&txid_vout) {
+        Ok((txid, vout)) => OutpointValidation { valid: true, txid: Some(txid), vout: Some(vout), error: None },
+        Err(e) => OutpointValidation { valid: false, txid: None, vout: None, error: Some(e) },
+    }
+}
+
+// -----
+// Wallet file associations
+// -----
+
+/// Extension of wallet backup files the app is registered to open.
+static WALLET_FILE_EXTENSION: &str = "bsvwallet";
+
+/// Wallet files the app was asked to open that the renderer hasn't collected yet.
+static OPENED_FILES: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+fn is_wallet_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(WALLET_FILE_EXTENSION))
+}
+
+/// Queue wallet files for the renderer and notify it with `open-file`.
+/// Files passed at launch arrive before the renderer is listening, which is
+/// why they are also kept for `take_opened_files`.
+fn forward_opened_files(app_handle: &AppHandle, paths: impl IntoIterator<Item = PathBuf>) {
+    for path in paths.into_iter().filter(|p| is_wallet_file(p)) {
+        let path = path.display().to_string();
+        log_line!("Opening wallet file: {}", path);
+        OPENED_FILES.lock().unwrap().push(path.clone());
+        if let Err(err) = app_handle.emit_to(MAIN_WINDOW_NAME, "open-file", path) {
+            log_error!("Failed to emit open-file event: {:?}", err);
         }
+    }
+}
 
-        // Ensure the window is shown
-        if let Err(e) = window.show() {
-            eprintln!("(macOS) show error: {}", e);
+/// Return (and clear) the wallet files the app has been asked to open.
+#[tauri::command]
+fn take_opened_files() -> Vec<String> {
+    std::mem::take(&mut *OPENED_FILES.lock().unwrap())
+}
+
+fn handle_run_event(app_handle: &AppHandle, event: tauri::RunEvent) {
+    // macOS delivers double-clicked files to the running app as URLs.
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    if let tauri::RunEvent::Opened { urls } = event {
+        forward_opened_files(app_handle, urls.into_iter().filter_map(|url| url.to_file_path().ok()));
+        return;
+    }
+    // Clicking the dock icon brings back a window hidden by a minimized launch.
+    #[cfg(target_os = "macos")]
+    if let tauri::RunEvent::Reopen { has_visible_windows: false, .. } = event {
+        if let Some(window) = app_handle.get_webview_window(MAIN_WINDOW_NAME) {
+            let _ = window.show();
+            let _ = window.set_focus();
         }
+        return;
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    let _ = (app_handle, event);
+}
 
-        // Request user attention (bounces Dock icon)
-        if let Err(e) = window.request_user_attention(Some(tauri::UserAttentionType::Informational))
-        {
-            eprintln!("(macOS) request_user_attention error: {}", e);
+// -----
+// Clipboard
+// -----
+
+/// Copy a secret to the clipboard and, after `auto_clear_ms`, clear it
+/// again, but only if it still holds that exact text, so anything the user
+/// copied in the meantime is left alone.
+#[tauri::command]
+fn copy_sensitive(app_handle: AppHandle, text: String, auto_clear_ms: Option<u64>) -> Result<(), String> {
+    app_handle.clipboard().write_text(text.as_str()).map_err(|e| e.to_string())?;
+    if let Some(ms) = auto_clear_ms.filter(|ms| *ms > 0) {
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            let clipboard = app_handle.clipboard();
+            if clipboard.read_text().is_ok_and(|current| current == text) {
+                if let Err(e) = clipboard.clear() {
+                    log_error!("Failed to clear sensitive clipboard contents: {}", e);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+// -----
+// Launch at login
+// -----
+
+/// Passed to the app when the OS starts it at login, so it comes up without
+/// a visible window and just serves requests.
+static MINIMIZED_ARG: &str = "--minimized";
+
+#[tauri::command]
+fn set_launch_at_login(app_handle: AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app_handle.autolaunch();
+    if enabled {
+        autolaunch.enable()
+    } else {
+        autolaunch.disable()
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn is_launch_at_login(app_handle: AppHandle) -> Result<bool, String> {
+    app_handle.autolaunch().is_enabled().map_err(|e| e.to_string())
+}
+
+// -----
+// Shutdown
+// -----
+
+/// Cancelled when the app begins a graceful shutdown; stops the HTTP server
+/// from accepting new connections.
+static SHUTDOWN: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+
+/// How long `quit_app` waits for in-flight renderer requests to finish.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Quit the whole app (not just close the window): stop the server, give
+/// pending requests a moment to complete, flush output, then exit.
+#[tauri::command]
+async fn quit_app(app_handle: AppHandle, pending_requests: State<'_, Arc<PendingMap>>) -> Result<(), String> {
+    log_line!("Shutting down");
+    SHUTDOWN.cancel();
+
+    let deadline = Instant::now() + SHUTDOWN_DRAIN_TIMEOUT;
+    while !pending_requests.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    if !pending_requests.is_empty() {
+        log_error!("Exiting with {} request(s) still pending", pending_requests.len());
+    }
+
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().flush();
+    app_handle.exit(0);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct MonitorPlacement {
+    /// Name of the monitor the window ended up on, if the platform gives one.
+    monitor: Option<String>,
+    /// True when `index` wasn't connected and the primary monitor was used.
+    fell_back_to_primary: bool,
+}
+
+/// Center the window on the monitor at `index` in the platform's monitor
+/// list. If no monitor has that index (e.g. it was disconnected since the
+/// index was saved), the primary monitor is used instead.
+#[tauri::command]
+fn move_to_monitor(window: Window, index: usize) -> Result<MonitorPlacement, String> {
+    let monitors = window.available_monitors().map_err(|e| e.to_string())?;
+    let (monitor, fell_back_to_primary) = match monitors.get(index) {
+        Some(monitor) => (monitor.clone(), false),
+        None => {
+            log_error!(
+                "Monitor index {} out of range ({} connected); using the primary monitor",
+                index,
+                monitors.len()
+            );
+            let primary = primary_or_first_monitor(&window, &monitors).ok_or("no monitor is connected")?;
+            (primary, true)
         }
+    };
 
-        // Focus the window - try multiple times with delays if needed
-        for i in 0..3 {
-            if let Ok(focused) = window.is_focused() {
-                if focused {
+    // Shrink the window if it is larger than the target monitor.
+    let mut size = window.outer_size().map_err(|e| e.to_string())?;
+    let (mw, mh) = (monitor.size().width, monitor.size().height);
+    if size.width > mw || size.height > mh {
+        size = PhysicalSize::new(size.width.min(mw), size.height.min(mh));
+        window.set_size(size).map_err(|e| e.to_string())?;
+    }
+
+    let x = monitor.position().x + ((mw - size.width) / 2) as i32;
+    let y = monitor.position().y + ((mh - size.height) / 2) as i32;
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())?;
+    Ok(MonitorPlacement { monitor: monitor.name().cloned(), fell_back_to_primary })
+}
+
+// -----
+// HTTP request routing
+// -----
+
+/// Where requests under a path prefix are sent.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum RouteTarget {
+    /// Forwarded to the renderer as an `http-request` event (the default).
+    Renderer,
+    /// `?url=` is fetched server-side through `proxy_fetch_manifest`.
+    Proxy,
+    /// Files are served from `dir`, with the prefix stripped from the path.
+    Static { dir: PathBuf },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Route {
+    prefix: String,
+    target: RouteTarget,
+}
+
+/// Parse `MND_ROUTES`, e.g. `/proxy/=proxy,/static/=static:/srv/files`.
+/// Invalid entries are logged and skipped.
+fn parse_routes(value: &str) -> Vec<Route> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry.split_once('=').and_then(|(prefix, target)| {
+                let target = match target {
+                    "renderer" => RouteTarget::Renderer,
+                    "proxy" => RouteTarget::Proxy,
+                    _ => RouteTarget::Static { dir: PathBuf::from(target.strip_prefix("static:")?) },
+                };
+                prefix.starts_with('/').then(|| Route { prefix: prefix.to_string(), target })
+            });
+            if parsed.is_none() {
+                log_error!("Ignoring invalid route: {:?}", entry);
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// The route with the longest prefix matching `path`, and the remainder of
+/// the path after that prefix. Unmatched paths go to the renderer.
+fn route_for(path: &str) -> (RouteTarget, String) {
+    config()
+        .routes
+        .into_iter()
+        .filter(|route| path.starts_with(&route.prefix))
+        .max_by_key(|route| route.prefix.len())
+        .map(|route| (route.target, path[route.prefix.len()..].to_string()))
+        .unwrap_or((RouteTarget::Renderer, path.to_string()))
+}
+
+static DEBUG_ECHO_PATH: &str = "/debug/echo";
+
+/// Echo the request body back with a 200, along with its `Content-Type` and
+/// any `X-Echo-*` headers. Never touches the renderer.
+async fn debug_echo(req: Request<Body>) -> Response<Body> {
+    let (parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+    let mut res = Response::new(Body::from(body));
+    for (name, value) in &parts.headers {
+        if name == hyper::header::CONTENT_TYPE || name.as_str().starts_with("x-echo-") {
+            res.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    add_cors_headers(&mut res);
+    res
+}
+
+/// The JSON an error response was built from, kept as a response extension
+/// so `plain_text_error` can re-render it.
+#[derive(Clone)]
+struct ErrorBody(serde_json::Value);
+
+/// Answer a request with a small JSON error body. Every error the server
+/// produces itself goes through here.
+fn json_error_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    let mut res = Response::new(Body::from(body.to_string()));
+    *res.status_mut() = status;
+    res.headers_mut().insert(hyper::header::CONTENT_TYPE, "application/json".parse().unwrap());
+    res.extensions_mut().insert(ErrorBody(body));
+    add_cors_headers(&mut res);
+    res
+}
+
+/// Whether the client wants plain-text errors: its `Accept` names
+/// `text/plain` but not `application/json`.
+fn prefers_plain_text(headers: &hyper::HeaderMap) -> bool {
+    let accept = headers
+        .get_all(hyper::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",")
+        .to_ascii_lowercase();
+    accept.contains("text/plain") && !accept.contains("application/json")
+}
+
+/// Re-render a server error as `code` or `code: message` in plain text.
+/// Responses that didn't come from `json_error_response` pass through.
+fn plain_text_error(res: Response<Body>) -> Response<Body> {
+    let Some(ErrorBody(body)) = res.extensions().get::<ErrorBody>().cloned() else {
+        return res;
+    };
+    let code = body.get("error").and_then(|v| v.as_str()).unwrap_or("error");
+    let text = match body.get("message").and_then(|v| v.as_str()) {
+        Some(message) => format!("{}: {}", code, message),
+        None => code.to_string(),
+    };
+    let (mut parts, _) = res.into_parts();
+    parts.headers.insert(hyper::header::CONTENT_TYPE, "text/plain; charset=utf-8".parse().unwrap());
+    parts.headers.remove(hyper::header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(text))
+}
+
+/// Serve a `proxy` route: fetch the `url` query parameter server-side.
+async fn proxy_route(req: &Request<Body>) -> Response<Body> {
+    let url = req
+        .uri()
+        .query()
+        .and_then(|query| url::form_urlencoded::parse(query.as_bytes()).find(|(k, _)| k == "url"))
+        .map(|(_, v)| v.into_owned());
+    let Some(url) = url else {
+        return json_error_response(StatusCode::BAD_REQUEST, serde_json::json!({ "error": "missing-url" }));
+    };
+    match proxy_fetch_manifest(url, None, None, None).await {
+        Ok(fetched) => {
+            let mut res = Response::new(Body::from(fetched.body));
+            *res.status_mut() = StatusCode::from_u16(fetched.status).unwrap_or(StatusCode::BAD_GATEWAY);
+            add_renderer_headers(&mut res, &fetched.headers);
+            add_cors_headers(&mut res);
+            res
+        }
+        Err(message) => {
+            json_error_response(StatusCode::BAD_GATEWAY, serde_json::json!({ "error": "proxy-failed", "message": message }))
+        }
+    }
+}
+
+/// Content type for a served file, by extension.
+fn static_content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("js") | Some("mjs") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("wasm") => "application/wasm",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Serve a `static` route from `dir`, refusing anything that resolves
+/// outside it.
+fn serve_static(dir: &Path, rest: &str) -> Response<Body> {
+    let not_found = || json_error_response(StatusCode::NOT_FOUND, serde_json::json!({ "error": "not-found" }));
+    let Ok(root) = dir.canonicalize() else {
+        return not_found();
+    };
+    let Ok(path) = root.join(rest.trim_start_matches('/')).canonicalize() else {
+        return not_found();
+    };
+    if !path.starts_with(&root) || !path.is_file() {
+        return not_found();
+    }
+    match fs::read(&path) {
+        Ok(contents) => {
+            let mut res = Response::new(Body::from(contents));
+            res.headers_mut().insert(hyper::header::CONTENT_TYPE, static_content_type(&path).parse().unwrap());
+            add_cors_headers(&mut res);
+            res
+        }
+        Err(e) => json_error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({ "error": "read-failed", "message": e.to_string() }),
+        ),
+    }
+}
+
+// -----
+// Server-Sent Events
+// -----
+
+/// Comment frame sent this often so proxies and the idle reaper see traffic.
+const SSE_HEARTBEAT: Duration = Duration::from_secs(15);
+/// Reconnect delay suggested to `EventSource` clients.
+const SSE_RETRY_MS: u64 = 3000;
+/// Frames buffered per subscription before slow clients start missing some.
+const SSE_BUFFER: usize = 64;
+
+/// Broadcast channels of formatted frames, keyed by subscription id.
+static SSE_CHANNELS: Lazy<DashMap<String, tokio::sync::broadcast::Sender<String>>> = Lazy::new(DashMap::new);
+
+/// One open event stream, as reported by `list_subscribers`.
+#[derive(Serialize, Clone)]
+struct Subscriber {
+    id: u64,
+    subscription: String,
+    path: String,
+    connected_at_unix_ms: u64,
+    remote_addr: String,
+}
+
+/// Open event streams, keyed by connection id.
+static SSE_SUBSCRIBERS: Lazy<DashMap<u64, Subscriber>> = Lazy::new(DashMap::new);
+static NEXT_SUBSCRIBER_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Currently connected event-stream clients, oldest first.
+#[tauri::command]
+fn list_subscribers() -> Vec<Subscriber> {
+    let mut subscribers: Vec<Subscriber> = SSE_SUBSCRIBERS.iter().map(|entry| entry.value().clone()).collect();
+    subscribers.sort_by_key(|subscriber| subscriber.id);
+    subscribers
+}
+
+/// Payload of an `sse-push` event from the renderer.
+#[derive(Deserialize)]
+struct SsePush {
+    subscription: String,
+    event: Option<String>,
+    id: Option<String>,
+    data: String,
+}
+
+/// Format one event-stream frame. Multi-line data becomes several `data:` lines.
+fn sse_frame(push: &SsePush) -> String {
+    let mut frame = String::new();
+    if let Some(event) = &push.event {
+        frame.push_str(&format!("event: {}\n", event.replace(['\r', '\n'], "")));
+    }
+    if let Some(id) = &push.id {
+        frame.push_str(&format!("id: {}\n", id.replace(['\r', '\n'], "")));
+    }
+    for line in push.data.lines() {
+        frame.push_str(&format!("data: {}\n", line));
+    }
+    if push.data.is_empty() {
+        frame.push_str("data: \n");
+    }
+    frame.push('\n');
+    frame
+}
+
+/// Fan an `sse-push` out to every client subscribed to its id. Pushes for
+/// subscriptions nobody is listening to are dropped.
+fn deliver_sse_push(push: SsePush) {
+    if let Some(channel) = SSE_CHANNELS.get(&push.subscription) {
+        let _ = channel.send(sse_frame(&push));
+    }
+}
+
+/// Open an event stream for `?subscription=<id>` (default "default"). The
+/// connection stays open, with heartbeats, until the client goes away or
+/// the app shuts down.
+fn serve_sse(req: &Request<Body>, remote_addr: SocketAddr, in_flight: InFlightGuard) -> Response<Body> {
+    let subscription = req
+        .uri()
+        .query()
+        .and_then(|query| url::form_urlencoded::parse(query.as_bytes()).find(|(k, _)| k == "subscription"))
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_else(|| "default".to_string());
+    let mut frames = SSE_CHANNELS
+        .entry(subscription.clone())
+        .or_insert_with(|| tokio::sync::broadcast::channel(SSE_BUFFER).0)
+        .subscribe();
+
+    let subscriber_id = NEXT_SUBSCRIBER_ID.fetch_add(1, Ordering::Relaxed);
+    SSE_SUBSCRIBERS.insert(
+        subscriber_id,
+        Subscriber {
+            id: subscriber_id,
+            subscription: subscription.clone(),
+            path: req.uri().path().to_string(),
+            connected_at_unix_ms: unix_millis(),
+            remote_addr: remote_addr.ip().to_string(),
+        },
+    );
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let _in_flight = in_flight;
+        let mut heartbeat = tokio::time::interval(SSE_HEARTBEAT);
+        heartbeat.tick().await;
+        let mut next = Some(format!("retry: {}\n\n", SSE_RETRY_MS));
+        loop {
+            if let Some(frame) = next.take() {
+                // Fails once the client has disconnected.
+                if sender.send_data(frame.into()).await.is_err() {
                     break;
                 }
             }
+            next = tokio::select! {
+                _ = SHUTDOWN.cancelled() => break,
+                _ = heartbeat.tick() => Some(": heartbeat\n\n".to_string()),
+                frame = frames.recv() => match frame {
+                    Ok(frame) => Some(frame),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                        Some(format!(": missed {} event(s)\n\n", missed))
+                    }
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+                },
+            };
+        }
+        drop(frames);
+        SSE_SUBSCRIBERS.remove(&subscriber_id);
+        SSE_CHANNELS.remove_if(&subscription, |_, channel| channel.receiver_count() == 0);
+    });
+
+    let mut res = Response::new(body);
+    let headers = res.headers_mut();
+    headers.insert(hyper::header::CONTENT_TYPE, "text/event-stream".parse().unwrap());
+    headers.insert(hyper::header::CACHE_CONTROL, "no-cache".parse().unwrap());
+    add_cors_headers(&mut res);
+    res
+}
+
+/// Delivers a serialized `http-request` payload to the renderer. Abstracted
+/// so the forwarding flow doesn't depend on a real webview.
+type EmitRequest = Arc<dyn Fn(String) -> tauri::Result<()> + Send + Sync>;
+
+/// Everything a request handler needs from the server, cloned per connection.
+#[derive(Clone)]
+struct RequestContext {
+    pending_requests: Arc<PendingMap>,
+    emit_request: EmitRequest,
+    request_counter: Arc<AtomicU64>,
+    remote_addr: SocketAddr,
+    /// Where oversized request bodies are spilled; `None` keeps them inline.
+    spill_dir: Option<PathBuf>,
+}
+
+/// A request body written to disk, removed when dropped.
+struct SpilledBody(PathBuf);
+
+impl Drop for SpilledBody {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// A request body, either in memory or spilled to a temp file.
+enum RequestBody {
+    Inline(hyper::body::Bytes),
+    Spilled { file: SpilledBody, len: usize },
+}
+
+/// Why a request body couldn't be read.
+#[derive(Debug)]
+enum BodyReadError {
+    /// The decompressed body exceeded `Config::max_decompressed_bytes`.
+    TooLarge,
+    Failed(String),
+}
+
+impl From<std::io::Error> for BodyReadError {
+    fn from(e: std::io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<DecompressedTooLarge>()) {
+            return BodyReadError::TooLarge;
+        }
+        BodyReadError::Failed(e.to_string())
+    }
+}
+
+/// Raised by `CappedOutput` once decompressed output passes its limit.
+#[derive(Debug)]
+struct DecompressedTooLarge;
+
+impl std::fmt::Display for DecompressedTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("decompressed body too large")
+    }
+}
+
+impl std::error::Error for DecompressedTooLarge {}
+
+/// Where a decoder inflates into. Refuses any write that would take the
+/// stream's total output past `limit`, so a zip bomb is stopped mid-inflate
+/// instead of after a whole chunk has expanded in memory.
+struct CappedOutput {
+    buf: Vec<u8>,
+    written: usize,
+    limit: usize,
+}
+
+impl CappedOutput {
+    fn new(limit: usize) -> Self {
+        CappedOutput { buf: Vec::new(), written: 0, limit }
+    }
+}
+
+impl Write for CappedOutput {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.written + data.len() > self.limit {
+            return Err(std::io::Error::other(DecompressedTooLarge));
+        }
+        self.written += data.len();
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl From<hyper::Error> for BodyReadError {
+    fn from(e: hyper::Error) -> Self {
+        BodyReadError::Failed(e.to_string())
+    }
+}
+
+/// Decoder for a request's `Content-Encoding`. Only gzip and deflate are
+/// undone; anything else reaches the renderer as sent.
+enum BodyDecoder {
+    Identity,
+    Gzip(flate2::write::GzDecoder<CappedOutput>),
+    Deflate(flate2::write::ZlibDecoder<CappedOutput>),
+}
+
+impl BodyDecoder {
+    /// A decoder for the request's encoding whose total output is capped at
+    /// `limit` bytes.
+    fn for_headers(headers: &hyper::HeaderMap, limit: usize) -> Self {
+        let encoding = headers
+            .get(hyper::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim().to_ascii_lowercase());
+        match encoding.as_deref() {
+            Some("gzip") | Some("x-gzip") => BodyDecoder::Gzip(flate2::write::GzDecoder::new(CappedOutput::new(limit))),
+            Some("deflate") => BodyDecoder::Deflate(flate2::write::ZlibDecoder::new(CappedOutput::new(limit))),
+            _ => BodyDecoder::Identity,
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        matches!(self, BodyDecoder::Identity)
+    }
+
+    /// Feed `chunk` through and return whatever output it produced.
+    fn decode(&mut self, chunk: &[u8]) -> Result<Vec<u8>, BodyReadError> {
+        match self {
+            BodyDecoder::Identity => Ok(chunk.to_vec()),
+            BodyDecoder::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                Ok(std::mem::take(&mut decoder.get_mut().buf))
+            }
+            BodyDecoder::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                Ok(std::mem::take(&mut decoder.get_mut().buf))
+            }
+        }
+    }
 
-            if let Err(e) = window.set_focus() {
-                eprintln!("(macOS) set_focus attempt {} error: {}", i, e);
+    /// Flush the stream's tail once the body has ended.
+    fn finish(&mut self) -> Result<Vec<u8>, BodyReadError> {
+        match self {
+            BodyDecoder::Identity => Ok(Vec::new()),
+            BodyDecoder::Gzip(decoder) => {
+                decoder.try_finish()?;
+                Ok(std::mem::take(&mut decoder.get_mut().buf))
+            }
+            BodyDecoder::Deflate(decoder) => {
+                decoder.try_finish()?;
+                Ok(std::mem::take(&mut decoder.get_mut().buf))
             }
-
-            // Small delay to allow macOS to process the focus request
-            std::thread::sleep(std::time::Duration::from_millis(50));
         }
     }
+}
 
-    #[cfg(target_os = "windows")]
-    {
-        // Show the window if it's hidden
-        if let Err(e) = window.show() {
-            eprintln!("(Windows) show error: {}", e);
-        }
-        // Unminimize the window (important!)
-        if let Err(e) = window.unminimize() {
-            eprintln!("(Windows) unminimize error: {}", e);
+/// Collects a body in memory, moving it to a file in `spill_dir` once it
+/// grows past `Config::spill_body_bytes`.
+struct BodySink<'a> {
+    spill_dir: Option<&'a Path>,
+    request_id: u64,
+    threshold: usize,
+    /// Largest body accepted, if capped.
+    limit: Option<usize>,
+    buffered: Vec<u8>,
+    spilled: Option<(SpilledBody, tokio::fs::File)>,
+    len: usize,
+}
+
+impl<'a> BodySink<'a> {
+    fn new(spill_dir: Option<&'a Path>, request_id: u64, limit: Option<usize>) -> Self {
+        BodySink {
+            spill_dir,
+            request_id,
+            threshold: config().spill_body_bytes,
+            limit,
+            buffered: Vec::new(),
+            spilled: None,
+            len: 0,
         }
-        // Attempt to focus the window directly
-        if let Err(e) = window.set_focus() {
-            eprintln!("(Windows) set_focus error: {}", e);
+    }
+
+    async fn push(&mut self, data: &[u8]) -> Result<(), BodyReadError> {
+        use tokio::io::AsyncWriteExt;
+
+        self.len += data.len();
+        if self.limit.is_some_and(|limit| self.len > limit) {
+            return Err(BodyReadError::TooLarge);
         }
-        // Temporarily set always-on-top to force focus
-        if let Err(e) = window.set_always_on_top(true) {
-            eprintln!("(Windows) set_always_on_top(true) error: {}", e);
+        if let Some((_, file)) = self.spilled.as_mut() {
+            file.write_all(data).await?;
+            return Ok(());
         }
-        // Remove always-on-top after focusing
-        if let Err(e) = window.set_always_on_top(false) {
-            eprintln!("(Windows) set_always_on_top(false) error: {}", e);
+        self.buffered.extend_from_slice(data);
+        if let Some(dir) = self.spill_dir.filter(|_| self.buffered.len() > self.threshold) {
+            fs::create_dir_all(dir)?;
+            let path = dir.join(format!("request-{}-{}.body", std::process::id(), self.request_id));
+            let guard = SpilledBody(path.clone());
+            let mut file = tokio::fs::File::create(&path).await?;
+            file.write_all(&self.buffered).await?;
+            self.buffered = Vec::new();
+            self.spilled = Some((guard, file));
         }
+        Ok(())
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        // First, unminimize the window if it's minimized
-        if let Err(e) = window.unminimize() {
-            eprintln!("(Linux) unminimize error: {}", e);
-        }
+    async fn finish(self) -> Result<RequestBody, BodyReadError> {
+        use tokio::io::AsyncWriteExt;
 
-        // Show the window if it's hidden
-        if let Err(e) = window.show() {
-            eprintln!("(Linux) show error: {}", e);
+        match self.spilled {
+            Some((guard, mut file)) => {
+                file.flush().await?;
+                Ok(RequestBody::Spilled { file: guard, len: self.len })
+            }
+            None => Ok(RequestBody::Inline(self.buffered.into())),
         }
+    }
+}
 
-        // Attempt to focus the window
-        if let Err(e) = window.set_focus() {
-            eprintln!("(Linux) set_focus error: {}", e);
-        }
+/// Read a request body, undoing any gzip/deflate `Content-Encoding` and
+/// spilling it to a file in `spill_dir` once it grows past
+/// `Config::spill_body_bytes`, so huge uploads don't sit in memory.
+/// Decompressed bodies are capped while inflating, so a small zip bomb can't
+/// fill memory or the disk.
+async fn read_request_body(
+    mut body: Body,
+    mut decoder: BodyDecoder,
+    spill_dir: Option<&Path>,
+    request_id: u64,
+) -> Result<RequestBody, BodyReadError> {
+    use hyper::body::HttpBody;
 
-        // On Linux, sometimes we need multiple focus attempts
-        std::thread::sleep(std::time::Duration::from_millis(30));
-        if let Ok(focused) = window.is_focused() {
-            if !focused {
-                if let Err(e) = window.set_focus() {
-                    eprintln!("(Linux) set_focus retry error: {}", e);
-                }
-            }
-        }
+    let limit = (!decoder.is_identity()).then(|| config().max_decompressed_bytes);
+    let mut sink = BodySink::new(spill_dir, request_id, limit);
+    while let Some(chunk) = body.data().await {
+        sink.push(&decoder.decode(&chunk?)?).await?;
     }
+    sink.push(&decoder.finish()?).await?;
+    sink.finish().await
 }
 
-/// Attempt to move the window out of the user's way so they can resume
-/// other tasks. The exact behavior (switch/minimize) differs per platform.
-#[tauri::command]
-fn relinquish_focus(window: Window) {
-    #[cfg(target_os = "linux")]
-    {
-        // Minimize the window instead of hiding
-        if let Err(e) = window.minimize() {
-            eprintln!("Linux minimize error: {}", e);
-        }
+/// Forward a request to the renderer as an `http-request` event and wait
+/// for its `ts-response`.
+async fn forward_to_renderer(req: Request<Body>, ctx: &RequestContext) -> Response<Body> {
+    let RequestContext { pending_requests, emit_request, request_counter, remote_addr, spill_dir } = ctx;
+
+    // Don't forward anything until the renderer is listening.
+    if !RENDERER_READY.load(Ordering::Relaxed) {
+        let mut res = json_error_response(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "error": "starting" }));
+        res.headers_mut().insert(hyper::header::RETRY_AFTER, "1".parse().unwrap());
+        return res;
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        // Minimize the window instead of hiding
-        if let Err(e) = window.minimize() {
-            eprintln!("Windows minimize error: {}", e);
+    // Hold new requests back while the renderer is draining.
+    if FORWARDING_PAUSED.load(Ordering::Relaxed) {
+        let mut res = json_error_response(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "error": "paused" }));
+        res.headers_mut().insert(hyper::header::RETRY_AFTER, "1".parse().unwrap());
+        return res;
+    }
+
+    // Hyper sends the interim `100 Continue` itself once we start
+    // reading the body, so only unsupported expectations need
+    // handling here (RFC 9110 §10.1.1).
+    if let Some(expect) = req.headers().get(hyper::header::EXPECT) {
+        let is_continue = expect
+            .to_str()
+            .map(|v| v.eq_ignore_ascii_case("100-continue"))
+            .unwrap_or(false);
+        if !is_continue {
+            return json_error_response(StatusCode::EXPECTATION_FAILED, serde_json::json!({ "error": "expectation-failed" }));
         }
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        // Try to restore focus to previous app
-        let prev_bundle_id = {
-            let prev = PREV_BUNDLE_ID.lock().unwrap();
-            prev.clone()
-        };
-        if let Some(bundle_id) = prev_bundle_id {
-            if !bundle_id.is_empty() && bundle_id != "com.apple.finder" {
-                let script = format!("tell application id \"{}\" to activate", bundle_id);
-                if let Err(e) = Command::new("osascript").arg("-e").arg(&script).output() {
-                    eprintln!("MacOS failed to re-activate previous app: {}", e);
-                }
+    // Enforce the origin's concurrency limit; the permit is released when
+    // this request finishes.
+    let _origin_permit = match admit_origin(req.headers()) {
+        OriginAdmission::Admitted(permit) => permit,
+        OriginAdmission::Busy => {
+            let mut res = json_error_response(StatusCode::TOO_MANY_REQUESTS, serde_json::json!({ "error": "origin-busy" }));
+            res.headers_mut().insert(hyper::header::RETRY_AFTER, "1".parse().unwrap());
+            return res;
+        }
+    };
+
+    // Any forwarded request counts as activity for auto-lock.
+    touch_activity();
+
+    // Generate a unique request ID.
+    let request_id = request_counter.fetch_add(1, Ordering::Relaxed);
+
+    // Extract the HTTP method, URI, and headers.
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let http_version = format!("{:?}", req.version());
+    // A gzip/deflate body is decompressed before it reaches the renderer,
+    // so its encoding and length headers no longer apply.
+    let decoder = BodyDecoder::for_headers(req.headers(), config().max_decompressed_bytes);
+    let decoded = !decoder.is_identity();
+    let headers = req.headers().iter()
+        .filter(|(k, _)| !(decoded && (*k == hyper::header::CONTENT_ENCODING || *k == hyper::header::CONTENT_LENGTH)))
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect::<Vec<(String, String)>>();
+    let renderer_timeout = renderer_timeout(uri.path(), req.headers());
+    let cache_headers = req.headers().clone();
+
+    // Replay or hold back retried POSTs carrying an Idempotency-Key,
+    // so a retry after a timeout can't run a wallet action twice.
+    let idempotency_key = (method == hyper::Method::POST)
+        .then(|| req.headers().get("idempotency-key"))
+        .flatten()
+        .and_then(|v| v.to_str().ok())
+        .map(|key| idempotency_cache_key(req.headers(), uri.path(), key));
+    if let Some(key) = &idempotency_key {
+        let ttl = Duration::from_secs(config().idempotency_ttl_secs);
+        IDEMPOTENCY_CACHE.retain(|_, entry| !entry.is_expired(ttl));
+        IDEMPOTENT_REQUESTS.retain(|_, key| IDEMPOTENCY_CACHE.contains_key(key));
+        match IDEMPOTENCY_CACHE.entry(key.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(entry) => {
+                return match entry.get() {
+                    IdempotencyEntry::Completed { status, body, headers, status_text, .. } => {
+                        let mut res = renderer_response(*status, body.clone(), headers, status_text.as_deref());
+                        res.headers_mut().insert("Idempotent-Replayed", "true".parse().unwrap());
+                        add_cors_headers(&mut res);
+                        res
+                    }
+                    IdempotencyEntry::InFlight { .. } => {
+                        json_error_response(StatusCode::CONFLICT, serde_json::json!({ "error": "request-in-progress" }))
+                    }
+                };
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(IdempotencyEntry::InFlight { since: Instant::now() });
+                IDEMPOTENT_REQUESTS.insert(request_id, key.clone());
             }
         }
-        _ = window.is_focused();
     }
-}
 
-#[command]
-async fn download(app_handle: AppHandle, filename: String, content: Vec<u8>) -> Result<(), String> {
-    let downloads_dir = app_handle
-        .path()
-        .download_dir()
-        .map_err(|e| e.to_string())?;
-    let path = PathBuf::from(downloads_dir);
+    // Read the request body. `_spilled` keeps a spilled body's file alive
+    // until the response has been sent.
+    let (body_str, body_file, request_bytes, _spilled) =
+        match read_request_body(req.into_body(), decoder, spill_dir.as_deref(), request_id).await {
+            Ok(RequestBody::Inline(bytes)) => (String::from_utf8_lossy(&bytes).to_string(), None, bytes.len(), None),
+            Ok(RequestBody::Spilled { file, len }) => {
+                (String::new(), Some(file.0.to_string_lossy().to_string()), len, Some(file))
+            }
+            Err(BodyReadError::TooLarge) => {
+                abandon_idempotent_request(request_id);
+                return json_error_response(StatusCode::PAYLOAD_TOO_LARGE, serde_json::json!({ "error": "body-too-large" }));
+            }
+            Err(BodyReadError::Failed(e)) => {
+                log_error!("Failed to read request body: {}", e);
+                abandon_idempotent_request(request_id);
+                return json_error_response(StatusCode::BAD_REQUEST, serde_json::json!({ "error": "bad-request" }));
+            }
+        };
 
-    // Split the filename into stem and extension (if any)
-    let path_obj = Path::new(&filename);
-    let stem = path_obj
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("file");
-    let ext = path_obj.extension().and_then(|e| e.to_str()).unwrap_or("");
+    // Answer repeats of a cacheable response without the renderer. Requests
+    // with an idempotency key or a spilled body are never cached.
+    let cache_key = (idempotency_key.is_none() && body_file.is_none())
+        .then(|| response_cache_key(&method, &uri, &cache_headers, &body_str));
+    if let Some(res) = cache_key.and_then(cached_response) {
+        return res;
+    }
 
-    // Initial path attempt
-    let mut final_path = path.clone();
-    final_path.push(&filename);
+    // Create a oneshot channel for awaiting the frontend response.
+    let (tx, rx) = oneshot::channel::<TsResponse>();
+    pending_requests.insert(request_id, PendingRequest::new(tx));
 
-    // Check if file exists and increment if necessary
-    let mut counter = 1;
-    while final_path.exists() {
-        let new_filename = if ext.is_empty() {
-            format!("{} ({}).{}", stem, counter, ext)
-        } else {
-            format!("{} ({}).{}", stem, counter, ext)
-        };
-        final_path = path.clone();
-        final_path.push(new_filename);
-        counter += 1;
+    // Prepare the event payload.
+    let event_payload = HttpRequestEvent {
+        protocol_version: PROTOCOL_VERSION,
+        method: method.to_string(),
+        path: uri.to_string(),
+        headers,
+        body: body_str,
+        request_id,
+        remote_addr: remote_addr.ip().to_string(),
+        http_version,
+        body_file,
+    };
+
+    // Serialize the payload to JSON.
+    let event_json = match serde_json::to_string(&event_payload) {
+        Ok(json) => json,
+        Err(e) => {
+            log_error!("Failed to serialize HTTP event: {:?}", e);
+            record_error("renderer", format!("failed to serialize request event: {e}"));
+            // Remove pending request since we cannot proceed.
+            pending_requests.remove(&request_id);
+            abandon_idempotent_request(request_id);
+            return json_error_response(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({ "error": "internal" }));
+        }
+    };
+
+    // Emit the "http-request" event to the main window.
+    if let Err(err) = emit_request(event_json) {
+        log_error!("Failed to emit http-request event: {:?}", err);
+        record_error("renderer", format!("failed to emit http-request: {err}"));
+        pending_requests.remove(&request_id);
+        abandon_idempotent_request(request_id);
+        return json_error_response(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({ "error": "internal" }));
     }
 
-    fs::write(&final_path, content).map_err(|e| e.to_string())
+    // Wait asynchronously for the frontend's response.
+    let forwarded_at = Instant::now();
+    match tokio::time::timeout(renderer_timeout, rx).await {
+        Ok(Ok(ts_response)) => {
+            record_request_metrics(
+                method.as_str(),
+                uri.path(),
+                ts_response.status,
+                request_bytes,
+                ts_response.body.len(),
+                forwarded_at.elapsed(),
+            );
+            // An out-of-range status is a renderer bug; surface it
+            // instead of passing it off as a success.
+            let status = match renderer_status(ts_response.status) {
+                Some(status) => status,
+                None => {
+                    log_error!(
+                        "Renderer returned invalid status {} for request {}",
+                        ts_response.status, request_id
+                    );
+                    record_error(
+                        "renderer",
+                        format!("invalid status {} for {} {}", ts_response.status, method, uri.path()),
+                    );
+                    return json_error_response(
+                        StatusCode::BAD_GATEWAY,
+                        serde_json::json!({ "error": "invalid-renderer-status", "got": ts_response.status }),
+                    );
+                }
+            };
+            if let Some((key, ttl_ms)) = cache_key.zip(ts_response.cache_ttl_ms.filter(|ms| *ms > 0)) {
+                store_cached_response(key, ttl_ms, status, &ts_response.body, &ts_response.headers);
+            }
+            let mut res = renderer_response(
+                status,
+                ts_response.body,
+                &ts_response.headers,
+                ts_response.status_text.as_deref(),
+            );
+            // Redirects pass through as-is; a 3xx without a Location
+            // leaves the client stranded, so flag it.
+            if res.status().is_redirection()
+                && res.status() != StatusCode::NOT_MODIFIED
+                && !res.headers().contains_key(hyper::header::LOCATION)
+            {
+                log_error!(
+                    "WARN renderer sent {} without a Location header for {} {}",
+                    res.status().as_u16(), method, uri.path()
+                );
+            }
+            // Append CORS headers
+            add_cors_headers(&mut res);
+            res
+        }
+        Ok(Err(err)) => {
+            // The sender was dropped: cancelled by the renderer or swept.
+            log_error!("Request {} was cancelled before a response: {:?}", request_id, err);
+            record_error("cancelled", format!("{} {} cancelled before a response", method, uri.path()));
+            json_error_response(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({ "error": "cancelled" }))
+        }
+        Err(_) => {
+            log_error!(
+                "Timed out after {}ms waiting for frontend response for request {}",
+                renderer_timeout.as_millis(),
+                request_id
+            );
+            record_error(
+                "timeout",
+                format!("{} {} timed out after {}ms", method, uri.path(), renderer_timeout.as_millis()),
+            );
+            pending_requests.remove(&request_id);
+            json_error_response(StatusCode::GATEWAY_TIMEOUT, serde_json::json!({ "error": "timeout" }))
+        }
+    }
 }
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![MINIMIZED_ARG]),
+        ))
+        .register_uri_scheme_protocol(PREVIEW_SCHEME, |_ctx, request| {
+            preview_protocol_response(&request)
+        })
         .setup(|app| {
+            app.manage(StartTime { instant: Instant::now(), unix_ms: unix_millis() });
+
             // Extract the main window.
             let main_window = app.get_webview_window(MAIN_WINDOW_NAME).unwrap();
 
+            // Started at login: keep serving requests without showing a window.
+            if std::env::args().any(|arg| arg == MINIMIZED_ARG) {
+                let _ = main_window.hide();
+            }
+
+            // Restore the saved window geometry and keep it up to date.
+            if let Some(geometry) = load_window_geometry(app.handle()) {
+                restore_window_geometry(&main_window, geometry);
+            }
+            {
+                let window = main_window.clone();
+                main_window.on_window_event(move |event| {
+                    if let WindowEvent::Moved(_) | WindowEvent::Resized(_) | WindowEvent::CloseRequested { .. } = event {
+                        save_window_geometry(&window);
+                    }
+                    if let WindowEvent::Focused(true) = event {
+                        touch_activity();
+                    }
+                    if let WindowEvent::ThemeChanged(theme) = event {
+                        if let Err(err) = window.emit("theme-changed", theme_name(Some(*theme))) {
+                            log_error!("Failed to emit theme-changed event: {:?}", err);
+                        }
+                    }
+                });
+            }
+
+            // Start the idle auto-lock timer.
+            tauri::async_runtime::spawn(run_auto_lock_timer(main_window.clone()));
+            #[cfg(debug_assertions)]
+            tauri::async_runtime::spawn(run_devtools_guard(main_window.clone()));
+
+            load_saved_config(app.handle());
+            load_origin_policies(app.handle());
+
             // Shared, concurrent map to store pending responses.
             let pending_requests: Arc<PendingMap> = Arc::new(DashMap::new());
+            app.manage(pending_requests.clone());
+            tauri::async_runtime::spawn(run_pending_sweeper(pending_requests.clone()));
             // Atomic counter to generate unique request IDs.
             let request_counter = Arc::new(AtomicU64::new(1));
+            app.manage(request_counter.clone());
 
             {
                 // Set up a listener for "ts-response" events coming from the frontend.
                 // We attach the listener to the main window (not globally) for security.
                 let pending_requests = pending_requests.clone();
-                main_window.listen("ts-response", move |event| {
+                let response_event = config().response_event;
+                main_window.listen(response_event.clone(), move |event| {
                     let payload = event.payload();
-                    if payload.len() > 0 {
+                    if !payload.is_empty() {
                         match serde_json::from_str::<TsResponse>(payload) {
-                            Ok(ts_response) => {
-                                if let Some((req_id, tx)) = pending_requests.remove(&ts_response.request_id) {
-                                    if let Err(err) = tx.send(ts_response) {
-                                        eprintln!(
-                                            "Failed to send response via oneshot channel for request {}: {:?}",
-                                            req_id, err
-                                        );
-                                    }
-                                } else {
-                                    eprintln!("Received ts-response for unknown request_id: {}", ts_response.request_id);
-                                }
-                            }
+                            Ok(ts_response) => deliver_ts_response(&pending_requests, ts_response),
                             Err(err) => {
-                                eprintln!("Failed to parse ts-response payload: {:?}", err);
+                                log_error!("Failed to parse ts-response payload: {:?}", err);
+                                record_error("renderer", format!("unparseable ts-response: {err}"));
                             }
                         }
                     } else {
-                        eprintln!("ts-response event did not include a payload");
+                        log_error!("{} event did not include a payload", response_event);
                     }
                 });
             }
 
+            // The renderer pushes Server-Sent Events to subscribed clients.
+            main_window.listen("sse-push", |event| match serde_json::from_str::<SsePush>(event.payload()) {
+                Ok(push) => deliver_sse_push(push),
+                Err(err) => log_error!("Failed to parse sse-push payload: {:?}", err),
+            });
+
+            // Windows and Linux pass files to open as launch arguments.
+            forward_opened_files(app.handle(), std::env::args_os().skip(1).map(PathBuf::from));
+
+            // The renderer announces when its http-request listener is ready.
+            main_window.listen("renderer-ready", |_event| {
+                RENDERER_READY.store(true, Ordering::Relaxed);
+            });
+
+            {
+                // Requests the renderer flags as needing approval are routed to
+                // the dedicated approval window instead of the main UI.
+                let app_handle = app.handle().clone();
+                main_window.listen("needs-approval", move |event| {
+                    let payload = event.payload().to_string();
+                    let app_handle = app_handle.clone();
+                    // Window creation deadlocks on Windows inside event handlers,
+                    // so do it off the listener.
+                    tauri::async_runtime::spawn(async move {
+                        show_approval_window(&app_handle, payload);
+                    });
+                });
+            }
+
             // Spawn a separate thread to run our asynchronous HTTP server.
             let main_window_clone = main_window.clone();
             let pending_requests_clone = pending_requests.clone();
             let request_counter_clone = request_counter.clone();
+            let spill_dir = app.path().app_data_dir().ok().map(|dir| dir.join("request-bodies"));
             std::thread::spawn(move || {
                 // Build a multi-threaded Tokio runtime.
                 let rt = tokio::runtime::Builder::new_multi_thread()
@@ -377,142 +4959,171 @@ fn main() {
 
                 rt.block_on(async move {
                     // Bind the Hyper server to 127.0.0.1:3321.
-                    let addr: SocketAddr = "127.0.0.1:3321".parse().expect("Invalid socket address");
-                    println!("HTTP server listening on http://{}", addr);
+                    let addr = SocketAddr::from(([127, 0, 0, 1], PREFERRED_PORT));
+                    log_line!("HTTP server listening on http://{}", addr);
 
                     // Attempt to bind the server and check for address in use error
-                    match Server::try_bind(&addr) {
-                        Ok(builder) => {
+                    match AddrIncoming::bind(&addr) {
+                        Ok(mut incoming) => {
+                            let local_addr = incoming.local_addr();
+                            let _ = BOUND_ADDR.set(local_addr);
+                            let _ = BIND_DIAGNOSTICS.set(BindDiagnostics {
+                                preferred_port: PREFERRED_PORT,
+                                preferred_port_taken: false,
+                                bound_port: Some(local_addr.port()),
+                                primary_instance: true,
+                            });
+                            if let Err(err) = main_window_clone.emit("server-listening", local_addr.port()) {
+                                log_error!("Failed to emit server-listening event: {:?}", err);
+                            }
+                            // Apply socket options to every accepted connection.
+                            let cfg = config();
+                            incoming.set_nodelay(cfg.tcp_nodelay);
+                            let keepalive = (cfg.tcp_keepalive_secs > 0).then(|| Duration::from_secs(cfg.tcp_keepalive_secs));
+                            incoming.set_keepalive(keepalive);
+                            log_line!(
+                                "Socket options: TCP_NODELAY={}, keepalive={}",
+                                cfg.tcp_nodelay,
+                                keepalive.map_or("off".to_string(), |d| format!("{}s", d.as_secs()))
+                            );
+                            // Wrap each accepted connection so idle ones get reaped.
+                            let idle_timeout = Duration::from_secs(config().idle_connection_secs);
+                            let acceptor = hyper::server::accept::poll_fn(move |cx| {
+                                Pin::new(&mut incoming).poll_accept(cx).map(|conn| {
+                                    conn.map(|res| res.map(|stream| IdleTimeoutStream::new(stream, idle_timeout)))
+                                })
+                            });
+                            let builder = Server::builder(acceptor);
+
+                            let emit_request: EmitRequest = {
+                                let main_window = main_window_clone.clone();
+                                let request_event = config().request_event;
+                                Arc::new(move |payload| main_window.emit(&request_event, payload))
+                            };
+
                             // Create our Hyper service.
-                            let make_svc = make_service_fn(move |_conn| {
+                            let make_svc = make_service_fn(move |conn: &IdleTimeoutStream| {
+                                // Capture the peer address of this connection.
+                                let remote_addr = conn.remote_addr();
+                                let in_flight = conn.in_flight();
                                 // Clone handles for each connection.
-                                let pending_requests = pending_requests_clone.clone();
-                                let main_window = main_window_clone.clone();
-                                let request_counter = request_counter_clone.clone();
+                                let ctx = RequestContext {
+                                    pending_requests: pending_requests_clone.clone(),
+                                    emit_request: emit_request.clone(),
+                                    request_counter: request_counter_clone.clone(),
+                                    remote_addr,
+                                    spill_dir: spill_dir.clone(),
+                                };
 
                                 async move {
                                     Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
                                         // Clone per-request handles.
-                                        let pending_requests = pending_requests.clone();
-                                        let main_window = main_window.clone();
-                                        let request_counter = request_counter.clone();
+                                        let ctx = ctx.clone();
+                                        let in_flight = in_flight.clone();
+                                        let plain_text = prefers_plain_text(req.headers());
 
-                                        async move {
+                                        let handled = async move {
+                                            let in_flight = InFlightGuard::new(in_flight);
+
+                                            // Requests on kept-alive connections can still arrive while
+                                            // we drain; don't forward them into a renderer that's leaving.
+                                            if SHUTDOWN.is_cancelled() {
+                                                let mut res = json_error_response(
+                                                    StatusCode::SERVICE_UNAVAILABLE,
+                                                    serde_json::json!({ "error": "shutting-down" }),
+                                                );
+                                                res.headers_mut().insert(hyper::header::CONNECTION, "close".parse().unwrap());
+                                                return Ok::<_, Infallible>(res);
+                                            }
+
+                                            // Reject requests addressed to any other host name (DNS rebinding).
+                                            let host = req.headers().get(hyper::header::HOST).and_then(|v| v.to_str().ok());
+                                            if !is_allowed_host(host, local_addr) {
+                                                log_error!("Rejected request with unexpected Host header: {:?}", host);
+                                                return Ok::<_, Infallible>(json_error_response(
+                                                    StatusCode::FORBIDDEN,
+                                                    serde_json::json!({ "error": "invalid-host" }),
+                                                ));
+                                            }
 
                                             // Intercept any OPTIONS requests
                                             if req.method() == hyper::Method::OPTIONS {
+                                                return Ok::<_, Infallible>(preflight_response());
+                                            }
+
+                                            let builtin_key = builtin_path_key(req.uri().path());
+
+                                            // Answer incidental browser requests (favicon etc.) without
+                                            // bothering the renderer.
+                                            if config().swallow_paths.iter().any(|p| builtin_path_key(p) == builtin_key) {
                                                 let mut res = Response::new(Body::empty());
-                                                res.headers_mut().insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Headers", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Methods", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Expose-Headers", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
+                                                *res.status_mut() = StatusCode::NO_CONTENT;
+                                                add_cors_headers(&mut res);
                                                 return Ok::<_, Infallible>(res);
                                             }
 
-                                            // Generate a unique request ID.
-                                            let request_id = request_counter.fetch_add(1, Ordering::Relaxed);
-
-                                            // Extract the HTTP method, URI, and headers.
-                                            let method = req.method().clone();
-                                            let uri = req.uri().clone();
-                                            let headers = req.headers().iter()
-                                                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-                                                .collect::<Vec<(String, String)>>();
-
-                                            // Read the full request body.
-                                            let whole_body = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
-                                            let body_str = String::from_utf8_lossy(&whole_body).to_string();
-
-                                            // Create a oneshot channel for awaiting the frontend response.
-                                            let (tx, rx) = oneshot::channel::<TsResponse>();
-                                            pending_requests.insert(request_id, tx);
-
-                                            // Prepare the event payload.
-                                            let event_payload = HttpRequestEvent {
-                                                method: method.to_string(),
-                                                path: uri.to_string(),
-                                                headers,
-                                                body: body_str,
-                                                request_id,
-                                            };
-
-                                            // Serialize the payload to JSON.
-                                            let event_json = match serde_json::to_string(&event_payload) {
-                                                Ok(json) => json,
-                                                Err(e) => {
-                                                    eprintln!("Failed to serialize HTTP event: {:?}", e);
-                                                    let mut res = Response::new(Body::from("Internal Server Error"));
-                                                    *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-                                                    // Append CORS headers
-                                                    res.headers_mut().insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Methods", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Expose-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
-                                                    // Remove pending request since we cannot proceed.
-                                                    pending_requests.remove(&request_id);
-                                                    return Ok::<_, Infallible>(res);
-                                                }
-                                            };
-
-                                            // Emit the "http-request" event to the main window.
-                                            if let Err(err) = main_window.emit("http-request", event_json) {
-                                                eprintln!("Failed to emit http-request event: {:?}", err);
-                                                pending_requests.remove(&request_id);
-                                                let mut res = Response::new(Body::from("Internal Server Error"));
-                                                *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-                                                // Append CORS headers
-                                                res.headers_mut().insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Headers", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Methods", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Expose-Headers", "*".parse().unwrap());
-                                                res.headers_mut().insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
+                                            if let Some(res) = uri_too_long(req.uri(), config().max_uri_bytes) {
                                                 return Ok::<_, Infallible>(res);
                                             }
 
-                                            // Wait asynchronously for the frontend's response.
-                                            match rx.await {
-                                                Ok(ts_response) => {
-                                                    let mut res = Response::new(Body::from(ts_response.body));
-                                                    *res.status_mut() = StatusCode::from_u16(ts_response.status)
-                                                        .unwrap_or(StatusCode::OK);
-                                                    // Append CORS headers
-                                                    res.headers_mut().insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Methods", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Expose-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
-                                                    Ok::<_, Infallible>(res)
-                                                }
-                                                Err(err) => {
-                                                    eprintln!("Error awaiting frontend response for request {}: {:?}", request_id, err);
-                                                    let mut res = Response::new(Body::from("Gateway Timeout"));
-                                                    *res.status_mut() = StatusCode::GATEWAY_TIMEOUT;
-                                                    // Append CORS headers
-                                                    res.headers_mut().insert("Access-Control-Allow-Origin", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Methods", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Expose-Headers", "*".parse().unwrap());
-                                                    res.headers_mut().insert("Access-Control-Allow-Private-Network", "true".parse().unwrap());
-                                                    Ok::<_, Infallible>(res)
-                                                }
+                                            // Serve runtime-registered built-in endpoints directly.
+                                            if let Some(endpoint) = BUILTIN_ENDPOINTS.get(&builtin_key) {
+                                                return Ok::<_, Infallible>(endpoint.respond());
+                                            }
+
+                                            let path = req.uri().path().to_string();
+
+                                            // Server-Sent Events stay open after the handler returns,
+                                            // so the stream takes over the in-flight guard.
+                                            if req.method() == hyper::Method::GET && builtin_key == builtin_path_key(&config().sse_path) {
+                                                return Ok(serve_sse(&req, ctx.remote_addr, in_flight));
+                                            }
+
+                                            if builtin_key == builtin_path_key(DEBUG_ECHO_PATH) && config().debug_endpoints {
+                                                return Ok(debug_echo(req).await);
+                                            }
+
+                                            match route_for(&path) {
+                                                (RouteTarget::Renderer, _) => Ok(forward_to_renderer(req, &ctx).await),
+                                                (RouteTarget::Proxy, _) => Ok(proxy_route(&req).await),
+                                                (RouteTarget::Static { dir }, rest) => Ok(serve_static(&dir, &rest)),
                                             }
+                                        };
+                                        // Errors are JSON unless the client asked for text.
+                                        async move {
+                                            let res = handled.await;
+                                            if plain_text { res.map(plain_text_error) } else { res }
                                         }
                                     }))
                                 }
                             });
 
                             // Build and run the Hyper server.
-                            let server = builder.serve(make_svc);
+                            let server = builder
+                                .serve(make_svc)
+                                .with_graceful_shutdown(SHUTDOWN.cancelled());
 
                             if let Err(e) = server.await {
-                                eprintln!("Server error: {}", e);
+                                log_error!("Server error: {}", e);
+                                record_error("server", e.to_string());
                             }
                         }
                         Err(e) => {
-                            eprintln!("Failed to bind server: {}", e);
-                            std::process::exit(1);
+                            log_error!("Failed to bind server: {}", e);
+                            record_error("bind", e.to_string());
+                            // If another instance holds the port, stay up so the UI can say so.
+                            let in_use = std::error::Error::source(&e)
+                                .and_then(|source| source.downcast_ref::<std::io::Error>())
+                                .is_some_and(|io| io.kind() == std::io::ErrorKind::AddrInUse);
+                            if !in_use {
+                                std::process::exit(1);
+                            }
+                            let _ = BIND_DIAGNOSTICS.set(BindDiagnostics {
+                                preferred_port: PREFERRED_PORT,
+                                preferred_port_taken: true,
+                                bound_port: None,
+                                primary_instance: false,
+                            });
                         }
                     }
                 });
@@ -525,13 +5136,557 @@ fn main() {
         is_focused,
         request_focus,
         relinquish_focus,
+        reset_focus_state,
+        set_dnd,
+        is_dnd,
+        list_windows,
+        focus_window,
+        get_pending_approval,
+        open_file_viewer,
+        get_resource_usage,
+        move_to_monitor,
+        quit_app,
+        take_opened_files,
+        validate_address,
+        validate_outpoint,
+        get_effective_config,
+        get_event_names,
+        get_server_port,
+        get_bind_diagnostics,
+        get_install_fingerprint,
+        kv_get,
+        kv_set,
+        get_download_volume_space,
+        get_network_diagnostics,
+        get_uptime,
+        get_recent_errors,
+        is_devtools_open,
+        set_devtools_allowed,
+        register_client_identity,
+        set_proxy_rate_limit,
+        ping_renderer,
+        benchmark_server,
+        cancel_pending_request,
+        set_path_timeout,
+        set_verbose_logging,
+        clear_caches,
+        register_fast_path,
+        unregister_fast_path,
+        set_activation_policy,
+        list_notification_sounds,
+        play_notification_sound,
+        copy_sensitive,
+        set_launch_at_login,
+        is_launch_at_login,
+        get_request_metrics,
+        create_support_bundle,
+        set_auto_lock_timeout,
+        get_auto_lock_timeout,
+        request_attention,
+        set_always_on_top,
+        set_visible_over_fullscreen,
+        set_resizable,
+        set_size_constraints,
+        get_system_theme,
+        get_latency_histogram,
+        reset_latency_histogram,
+        run_connectivity_wizard,
+        export_config,
+        import_config,
+        get_renderer_last_seen,
+        clear_response_cache,
+        check_clock_skew,
+        register_builtin_endpoint,
+        unregister_builtin_endpoint,
+        is_always_on_top,
         download,
         save_file,
-        proxy_fetch_manifest
+        read_file_chunk,
+        append_to_file,
+        proxy_fetch_manifest,
+        get_protocol_version,
+        probe_proxy_hosts,
+        test_proxy_host,
+        list_subscribers,
+        capture_state_snapshot,
+        resolve_download_path,
+        set_origin_policy,
+        get_origin_policies,
+        drain_requests,
+        resume_requests
     ])
     .plugin(tauri_plugin_opener::init())
     .plugin(tauri_plugin_shell::init())
     .plugin(tauri_plugin_dialog::init())
-    .run(tauri::generate_context!())
-    .expect("Error while running Tauri application");
-    }
\ No newline at end of file
+    .build(tauri::generate_context!())
+    .expect("Error while running Tauri application")
+    .run(handle_run_event);
+    }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `RequestContext` whose `http-request` events go to `respond`, with
+    /// the pending map and request id, instead of to a webview.
+    fn test_context(respond: impl Fn(&PendingMap, u64) + Send + Sync + 'static) -> RequestContext {
+        RENDERER_READY.store(true, Ordering::Relaxed);
+        let pending_requests: Arc<PendingMap> = Arc::new(DashMap::new());
+        let emit_request: EmitRequest = {
+            let pending_requests = pending_requests.clone();
+            Arc::new(move |payload| {
+                let event: serde_json::Value = serde_json::from_str(&payload).unwrap();
+                respond(&pending_requests, event["request_id"].as_u64().unwrap());
+                Ok(())
+            })
+        };
+        RequestContext {
+            pending_requests,
+            emit_request,
+            request_counter: Arc::new(AtomicU64::new(1)),
+            remote_addr: "127.0.0.1:1".parse().unwrap(),
+            spill_dir: None,
+        }
+    }
+
+    fn test_request(path: &str) -> hyper::http::request::Builder {
+        Request::builder().method("POST").uri(path)
+    }
+
+    fn error_code(res: &Response<Body>) -> String {
+        res.extensions().get::<ErrorBody>().unwrap().0["error"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn forward_returns_renderer_response() {
+        let ctx = test_context(|pending_requests, request_id| {
+            deliver_ts_response(
+                pending_requests,
+                TsResponse {
+                    request_id,
+                    status: 201,
+                    body: "created".into(),
+                    headers: vec![("X-Test".into(), "1".into())],
+                    status_text: None,
+                    cache_ttl_ms: None,
+                },
+            );
+        });
+        let req = test_request("/forward-success").body(Body::from("{}")).unwrap();
+        let res = forward_to_renderer(req, &ctx).await;
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()["x-test"], "1");
+        assert!(ctx.pending_requests.is_empty());
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"created");
+    }
+
+    #[tokio::test]
+    async fn forward_reports_dropped_request() {
+        let ctx = test_context(|pending_requests, request_id| {
+            pending_requests.remove(&request_id);
+        });
+        let req = test_request("/forward-dropped").body(Body::empty()).unwrap();
+        let res = forward_to_renderer(req, &ctx).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error_code(&res), "cancelled");
+    }
+
+    #[tokio::test]
+    async fn forward_times_out_without_response() {
+        let ctx = test_context(|_, _| {});
+        let req = test_request("/forward-timeout")
+            .header("X-MND-Timeout-Ms", "50")
+            .body(Body::empty())
+            .unwrap();
+        let res = forward_to_renderer(req, &ctx).await;
+        assert_eq!(res.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error_code(&res), "timeout");
+        assert!(ctx.pending_requests.is_empty());
+    }
+
+    #[test]
+    fn renderer_status_rejects_out_of_range_codes() {
+        assert_eq!(renderer_status(0), None);
+        assert_eq!(renderer_status(99), None);
+        assert_eq!(renderer_status(600), None);
+        assert_eq!(renderer_status(999), None);
+        assert_eq!(renderer_status(100), Some(StatusCode::CONTINUE));
+        assert_eq!(renderer_status(599), StatusCode::from_u16(599).ok());
+    }
+
+    #[tokio::test]
+    async fn forward_maps_invalid_renderer_status_to_502() {
+        for status in [0, 999] {
+            let ctx = test_context(move |pending_requests, request_id| {
+                deliver_ts_response(
+                    pending_requests,
+                    TsResponse {
+                        request_id,
+                        status,
+                        body: String::new(),
+                        headers: Vec::new(),
+                        status_text: None,
+                        cache_ttl_ms: None,
+                    },
+                );
+            });
+            let req = test_request("/forward-invalid-status").body(Body::empty()).unwrap();
+            let res = forward_to_renderer(req, &ctx).await;
+            assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+            assert_eq!(error_code(&res), "invalid-renderer-status");
+        }
+    }
+
+    fn header_pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn renderer_headers_keep_every_set_cookie() {
+        let headers = header_pairs(&[
+            ("Set-Cookie", "a=1; Path=/"),
+            ("Set-Cookie", "b=2; HttpOnly"),
+            ("Set-Cookie", "c=3"),
+            ("Content-Length", "999"),
+        ]);
+        let res = renderer_response(StatusCode::OK, String::new(), &headers, None);
+        let cookies: Vec<_> = res.headers().get_all(hyper::header::SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1; Path=/", "b=2; HttpOnly", "c=3"]);
+        assert!(!res.headers().contains_key(hyper::header::CONTENT_LENGTH));
+    }
+
+    #[tokio::test]
+    async fn forward_passes_redirects_through() {
+        let ctx = test_context(|pending_requests, request_id| {
+            deliver_ts_response(
+                pending_requests,
+                TsResponse {
+                    request_id,
+                    status: 302,
+                    body: String::new(),
+                    headers: header_pairs(&[("Location", "https://example.com/next")]),
+                    status_text: None,
+                    cache_ttl_ms: None,
+                },
+            );
+        });
+        let req = test_request("/forward-redirect").body(Body::empty()).unwrap();
+        let res = forward_to_renderer(req, &ctx).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+        assert_eq!(res.headers()[hyper::header::LOCATION], "https://example.com/next");
+    }
+
+    #[test]
+    fn uri_too_long_rejects_only_past_the_limit() {
+        let at_limit: hyper::Uri = format!("/{}?q=1", "a".repeat(60)).parse().unwrap();
+        assert_eq!(at_limit.path_and_query().unwrap().as_str().len(), 65);
+        assert!(uri_too_long(&at_limit, 65).is_none());
+
+        let res = uri_too_long(&at_limit, 64).unwrap();
+        assert_eq!(res.status(), StatusCode::URI_TOO_LONG);
+        assert_eq!(error_code(&res), "uri-too-long");
+    }
+
+    #[test]
+    fn renderer_response_ignores_invalid_status_text() {
+        let res = renderer_response(StatusCode::OK, String::new(), &[], Some("bad\r\nline"));
+        assert!(res.extensions().get::<hyper::ext::ReasonPhrase>().is_none());
+    }
+
+    #[tokio::test]
+    async fn renderer_status_text_reaches_the_status_line() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|_req| async {
+                Ok::<_, Infallible>(renderer_response(StatusCode::OK, "ok".into(), &[], Some("Awesome")))
+            }))
+        });
+        let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut raw = String::new();
+        stream.read_to_string(&mut raw).await.unwrap();
+        assert!(raw.starts_with("HTTP/1.1 200 Awesome\r\n"), "{}", raw);
+    }
+
+    #[test]
+    fn strict_utf8_body_rejects_invalid_bytes() {
+        assert_eq!(strict_utf8_body("{\"name\":\"ü\"}".as_bytes()).unwrap(), "{\"name\":\"ü\"}");
+        assert_eq!(
+            strict_utf8_body(b"{\"name\":\"\xff\"}").unwrap_err(),
+            "response is not valid UTF-8 at byte 9"
+        );
+    }
+
+    #[test]
+    fn path_keys_fold_trailing_slashes_and_case() {
+        assert_eq!(normalized_path_key("/favicon.ico", true), "/favicon.ico");
+        assert_eq!(normalized_path_key("/favicon.ico/", true), "/favicon.ico");
+        assert_eq!(normalized_path_key("/Favicon.ICO//", true), "/favicon.ico");
+        assert_eq!(normalized_path_key("/", true), "/");
+        assert_eq!(normalized_path_key("//", true), "/");
+        assert_eq!(normalized_path_key("/Favicon.ico/", false), "/Favicon.ico/");
+    }
+
+    #[test]
+    fn append_bytes_concatenates_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        assert_eq!(append_bytes(&path, b"first\n").unwrap(), 6);
+        assert_eq!(append_bytes(&path, b"second\n").unwrap(), 13);
+        assert_eq!(fs::read(&path).unwrap(), b"first\nsecond\n");
+    }
+
+    fn accept(value: &str) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::ACCEPT, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn errors_default_to_json() {
+        let res = json_error_response(StatusCode::BAD_REQUEST, serde_json::json!({ "error": "bad-request" }));
+        assert_eq!(res.headers()[hyper::header::CONTENT_TYPE], "application/json");
+        assert_eq!(error_code(&res), "bad-request");
+    }
+
+    #[test]
+    fn plain_text_only_when_json_is_not_accepted() {
+        assert!(!prefers_plain_text(&hyper::HeaderMap::new()));
+        assert!(!prefers_plain_text(&accept("*/*")));
+        assert!(!prefers_plain_text(&accept("application/json, text/plain")));
+        assert!(prefers_plain_text(&accept("text/plain")));
+        assert!(prefers_plain_text(&accept("Text/Plain;q=0.9, */*;q=0.1")));
+    }
+
+    #[tokio::test]
+    async fn plain_text_error_rewrites_json_errors() {
+        let res = plain_text_error(json_error_response(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({ "error": "upstream", "message": "refused" }),
+        ));
+        assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(res.headers()[hyper::header::CONTENT_TYPE], "text/plain; charset=utf-8");
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"upstream: refused");
+
+        let ok = plain_text_error(Response::new(Body::from("fine")));
+        assert!(!ok.headers().contains_key(hyper::header::CONTENT_TYPE));
+    }
+
+    fn upstream_headers(pairs: &[(&str, &str)]) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                reqwest::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    #[test]
+    fn proxy_headers_drop_hop_by_hop() {
+        let headers = upstream_headers(&[
+            ("connection", "keep-alive, x-session-hint"),
+            ("keep-alive", "timeout=5"),
+            ("transfer-encoding", "chunked"),
+            ("x-session-hint", "abc"),
+            ("content-type", "application/json"),
+            ("etag", "\"v1\""),
+        ]);
+        assert_eq!(
+            filter_proxy_headers(&headers, None),
+            header_pairs(&[("content-type", "application/json"), ("etag", "\"v1\"")])
+        );
+    }
+
+    #[test]
+    fn proxy_headers_honour_allowlist() {
+        let headers = upstream_headers(&[
+            ("content-type", "application/json"),
+            ("etag", "\"v1\""),
+            ("upgrade", "h2c"),
+        ]);
+        let allowlist = vec!["Content-Type".to_string(), "Upgrade".to_string()];
+        assert_eq!(
+            filter_proxy_headers(&headers, Some(&allowlist)),
+            header_pairs(&[("content-type", "application/json")])
+        );
+        assert!(filter_proxy_headers(&headers, Some(&[])).is_empty());
+    }
+
+    #[tokio::test]
+    async fn preflight_then_authorized_request() {
+        let preflight = preflight_response();
+        assert_eq!(preflight.status(), StatusCode::OK);
+        let allowed = preflight.headers()["access-control-allow-headers"].to_str().unwrap();
+        assert!(allowed.split(',').any(|h| h.trim().eq_ignore_ascii_case("authorization")));
+
+        let ctx = test_context(|pending_requests, request_id| {
+            deliver_ts_response(
+                pending_requests,
+                TsResponse {
+                    request_id,
+                    status: 200,
+                    body: String::new(),
+                    headers: Vec::new(),
+                    status_text: None,
+                    cache_ttl_ms: None,
+                },
+            );
+        });
+        let req = test_request("/preflight-then-request")
+            .header(hyper::header::AUTHORIZATION, "Bearer token")
+            .body(Body::empty())
+            .unwrap();
+        let res = forward_to_renderer(req, &ctx).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["access-control-allow-origin"], "*");
+    }
+
+    fn gzip_headers() -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        headers
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decoder_inflates_within_the_limit() {
+        let mut decoder = BodyDecoder::for_headers(&gzip_headers(), 1024);
+        let mut out = decoder.decode(&gzip(b"hello")).unwrap();
+        out.extend(decoder.finish().unwrap());
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn decoder_stops_inflating_at_the_limit() {
+        // ~8 KiB on the wire, 8 MiB once inflated.
+        let bomb = gzip(&vec![0; 8 * 1024 * 1024]);
+        let mut decoder = BodyDecoder::for_headers(&gzip_headers(), 64 * 1024);
+        assert!(matches!(decoder.decode(&bomb), Err(BodyReadError::TooLarge)));
+        let BodyDecoder::Gzip(inner) = &decoder else { unreachable!() };
+        assert!(inner.get_ref().written <= 64 * 1024);
+    }
+
+    #[test]
+    fn saved_config_fills_missing_fields_with_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, r#"{ "auto_lock_secs": 42, "renderer_timeout_ms": 5000 }"#).unwrap();
+
+        let saved = read_saved_config(&path).unwrap();
+        let defaults = Config::default();
+        assert_eq!(saved.auto_lock_secs, 42);
+        assert_eq!(saved.renderer_timeout_ms, 5000);
+        assert_eq!(saved.max_uri_bytes, defaults.max_uri_bytes);
+        assert_eq!(saved.sse_path, defaults.sse_path);
+        assert_eq!(saved.max_decompressed_bytes, defaults.max_decompressed_bytes);
+    }
+
+    #[test]
+    fn redirects_may_change_host_but_not_leave_https_or_the_internet() {
+        let check = |url: &str| check_redirect_target(&Url::parse(url).unwrap());
+        assert!(check("https://www.example.com/manifest.json").is_ok());
+        assert!(check("https://cdn.example.net/app/manifest.json").is_ok());
+        assert!(check("https://93.184.216.34/manifest.json").is_ok());
+
+        assert!(check("http://www.example.com/manifest.json").is_err());
+        for local in [
+            "https://localhost/manifest.json",
+            "https://wallet.localhost./manifest.json",
+            "https://127.0.0.1/manifest.json",
+            "https://10.0.0.5/manifest.json",
+            "https://192.168.1.1/manifest.json",
+            "https://169.254.169.254/manifest.json",
+            "https://100.100.0.1/manifest.json",
+            "https://[::1]/manifest.json",
+            "https://[fd00::1]/manifest.json",
+            "https://[fe80::1]/manifest.json",
+            "https://[::ffff:127.0.0.1]/manifest.json",
+        ] {
+            assert!(check(local).is_err(), "{}", local);
+        }
+    }
+
+    fn touch(dir: &Path, name: &str) {
+        fs::write(dir.join(name), b"").unwrap();
+    }
+
+    fn unique_name(dir: &Path, filename: &str) -> String {
+        let path = unique_path(dir, filename).unwrap();
+        assert_eq!(path.parent(), Some(dir));
+        path.file_name().unwrap().to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn unique_path_keeps_free_names() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(unique_name(dir.path(), "report.pdf"), "report.pdf");
+        assert_eq!(unique_name(dir.path(), "../report.pdf"), "report.pdf");
+    }
+
+    #[test]
+    fn unique_path_without_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "README");
+        assert_eq!(unique_name(dir.path(), "README"), "README (1)");
+    }
+
+    #[test]
+    fn unique_path_continues_existing_counter() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "report.pdf");
+        touch(dir.path(), "report (1).pdf");
+        assert_eq!(unique_name(dir.path(), "report.pdf"), "report (2).pdf");
+        assert_eq!(unique_name(dir.path(), "report (1).pdf"), "report (2).pdf");
+    }
+
+    #[test]
+    fn unique_path_fills_counter_gaps() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "file.txt");
+        touch(dir.path(), "file (1).txt");
+        touch(dir.path(), "file (3).txt");
+        assert_eq!(unique_name(dir.path(), "file.txt"), "file (2).txt");
+    }
+
+    #[test]
+    fn unique_path_keeps_parenthesised_digits_in_names() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "Movie (2019).mp4");
+        assert_eq!(unique_name(dir.path(), "Movie (2019).mp4"), "Movie (2019) (1).mp4");
+    }
+
+    #[test]
+    fn unique_path_numbers_before_dotted_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "my.notes.txt");
+        touch(dir.path(), "backup.v2.tar.gz");
+        touch(dir.path(), ".env");
+        assert_eq!(unique_name(dir.path(), "my.notes.txt"), "my.notes (1).txt");
+        assert_eq!(unique_name(dir.path(), "backup.v2.tar.gz"), "backup.v2 (1).tar.gz");
+        assert_eq!(unique_name(dir.path(), ".env"), ".env (1)");
+    }
+
+    #[test]
+    fn unique_path_handles_unicode_names() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "résumé 日本.pdf");
+        assert_eq!(unique_name(dir.path(), "résumé 日本.pdf"), "résumé 日本 (1).pdf");
+    }
+}
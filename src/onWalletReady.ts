@@ -26,6 +26,10 @@ import {
   WERR_REVIEW_ACTIONS
 } from '@bsv/sdk';
 import { listen, emit } from '@tauri-apps/api/event'
+import { invoke } from '@tauri-apps/api/core'
+
+// Event names used to talk to the backend; refreshed from its config on startup.
+let eventNames = { request_event: 'http-request', response_event: 'ts-response' }
 
 
 // Parse the origin header and turn it into a fqdn (e.g. projectbabbage.com:8080)
@@ -48,7 +52,7 @@ function parseOrigin(req, headers: Record<string, string>): string {
     return new URL(candidate).host;
   }
 
-  emit('ts-response', {
+  emit(eventNames.response_event, {
     request_id: req.request_id,
     status: 400,
     body: JSON.stringify({ message: 'Origin header is required' })
@@ -58,7 +62,8 @@ function parseOrigin(req, headers: Record<string, string>): string {
 
 
 export const onWalletReady = async (wallet: WalletInterface): Promise<(() => void) | undefined> => {
-  return await listen('http-request', async (event) => {
+  eventNames = await invoke('get_event_names')
+  const unlisten = await listen(eventNames.request_event, async (event) => {
     let response
 
     try {
@@ -792,9 +797,21 @@ export const onWalletReady = async (wallet: WalletInterface): Promise<(() => voi
       }
 
       // Emit the response back to Rust.
-      emit('ts-response', response)
+      emit(eventNames.response_event, response)
     } catch (e) {
       console.error("Error handling http-request event:", e)
     }
   })
+
+  // Answer latency probes from the diagnostics panel.
+  const unlistenPing = await listen('renderer-ping', (event) => {
+    emit(eventNames.response_event, { request_id: event.payload, status: 200, body: '' })
+  })
+
+  // Tell the backend the listener is in place so it starts forwarding requests.
+  await emit('renderer-ready')
+  return () => {
+    unlisten()
+    unlistenPing()
+  }
 }
\ No newline at end of file